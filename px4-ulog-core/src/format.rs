@@ -0,0 +1,175 @@
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Error of `parse_format`, borrowing the offending part of the format definition
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FormatError<'a> {
+    /// No `name:fields` structure, or an empty message name
+    InvalidFormat,
+    /// A field which is not a type and a name separated by a space
+    InvalidField(&'a str),
+    /// A type with an invalid array length, e.g. `float[x]`
+    InvalidType(&'a str),
+    /// A field name used more than once
+    DuplicateField(&'a str),
+}
+
+/// A field of a format definition, as written in the log
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FormatField<'a> {
+    /// The type without the array length, a primitive like `uint64_t` or a message name
+    pub type_name: &'a str,
+    /// The array length for array fields, e.g. 3 for `float[3]`
+    pub array_len: Option<i16>,
+    pub field_name: &'a str,
+}
+
+impl<'a> FormatField<'a> {
+    /// Number of bytes the field occupies, None if it is of a nested message type
+    pub fn size(&self) -> Option<usize> {
+        let count = match self.array_len {
+            Some(array_len) => usize::try_from(array_len).ok()?,
+            None => 1,
+        };
+        primitive_size(self.type_name).map(|size| size * count)
+    }
+}
+
+/// The content of a format message, e.g. `vehicle_status:uint64_t timestamp;uint8_t nav_state;`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MessageFormat<'a> {
+    pub message_name: &'a str,
+    pub fields: Vec<FormatField<'a>>,
+}
+
+impl<'a> MessageFormat<'a> {
+    /// Offset of the field in the data of a data message, after the msg_id. None if the field
+    /// does not exist or comes after a field of a nested message type, which needs flattening.
+    pub fn field_offset(&self, field_name: &str) -> Option<usize> {
+        let mut offset = 0;
+        for field in &self.fields {
+            if field.field_name == field_name {
+                return Some(offset);
+            }
+            offset += field.size()?;
+        }
+        None
+    }
+}
+
+/// Parses the content of a format message. Nested message types are not resolved.
+pub fn parse_format(format: &str) -> Result<MessageFormat<'_>, FormatError<'_>> {
+    let mut parts = format.split(':');
+    let (message_name, fields) = match (parts.next(), parts.next(), parts.next()) {
+        // The list of fields may be empty
+        (Some(message_name), Some(fields), None) if !message_name.is_empty() => {
+            (message_name, fields)
+        }
+        _ => return Err(FormatError::InvalidFormat),
+    };
+
+    let mut result = MessageFormat {
+        message_name,
+        fields: Vec::new(),
+    };
+    let mut field_names = BTreeSet::new();
+    for type_and_name in fields.split(';').filter(|s| !s.is_empty()) {
+        let mut split = type_and_name.split(' ');
+        let (written_type, field_name) = match (split.next(), split.next(), split.next()) {
+            (Some(written_type), Some(field_name), None)
+                if !written_type.is_empty() && !field_name.is_empty() =>
+            {
+                (written_type, field_name)
+            }
+            _ => return Err(FormatError::InvalidField(type_and_name)),
+        };
+        let (type_name, array_len) = parse_type(written_type)?;
+        if !field_names.insert(field_name) {
+            return Err(FormatError::DuplicateField(field_name));
+        }
+        result.fields.push(FormatField {
+            type_name,
+            array_len,
+            field_name,
+        });
+    }
+    Ok(result)
+}
+
+fn parse_type(written_type: &str) -> Result<(&str, Option<i16>), FormatError<'_>> {
+    let mut split = written_type.split('[');
+    match (split.next(), split.next(), split.next()) {
+        (Some(type_name), None, _) => Ok((type_name, None)),
+        (Some(type_name), Some(array_len), None) => array_len
+            .strip_suffix(']')
+            .and_then(|array_len| array_len.parse::<i16>().ok())
+            .map(|array_len| (type_name, Some(array_len)))
+            .ok_or(FormatError::InvalidType(written_type)),
+        _ => Err(FormatError::InvalidType(written_type)),
+    }
+}
+
+/// Number of bytes of a primitive type, None for other types, i.e. nested messages
+pub fn primitive_size(type_name: &str) -> Option<usize> {
+    match type_name {
+        "int8_t" | "uint8_t" | "bool" | "char" => Some(1),
+        "int16_t" | "uint16_t" => Some(2),
+        "int32_t" | "uint32_t" | "float" => Some(4),
+        "int64_t" | "uint64_t" | "double" => Some(8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parses_format() {
+        let format = parse_format("vehicle_status:uint64_t timestamp;float[3] q;gps x;").unwrap();
+        assert_eq!(format.message_name, "vehicle_status");
+        assert_eq!(
+            format.fields,
+            vec![
+                FormatField {
+                    type_name: "uint64_t",
+                    array_len: None,
+                    field_name: "timestamp"
+                },
+                FormatField {
+                    type_name: "float",
+                    array_len: Some(3),
+                    field_name: "q"
+                },
+                FormatField {
+                    type_name: "gps",
+                    array_len: None,
+                    field_name: "x"
+                },
+            ]
+        );
+        assert_eq!(format.field_offset("q"), Some(8));
+        assert_eq!(format.field_offset("x"), Some(20));
+        assert_eq!(format.field_offset("y"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_formats() {
+        assert_eq!(parse_format("no_fields"), Err(FormatError::InvalidFormat));
+        assert_eq!(parse_format(":uint8_t x;"), Err(FormatError::InvalidFormat));
+        assert_eq!(
+            parse_format("a:uint8_t;"),
+            Err(FormatError::InvalidField("uint8_t"))
+        );
+        assert_eq!(
+            parse_format("a:uint8_t[x] y;"),
+            Err(FormatError::InvalidType("uint8_t[x]"))
+        );
+        assert_eq!(
+            parse_format("a:uint8_t x;int8_t x;"),
+            Err(FormatError::DuplicateField("x"))
+        );
+    }
+}
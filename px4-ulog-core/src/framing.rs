@@ -0,0 +1,248 @@
+use alloc::vec::Vec;
+
+use crate::{LittleEndianParser, HEADER_LEN, ULOG_MAGIC};
+
+/// The ULog file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramingError {
+    /// The bytes do not start with the ULog magic
+    InvalidHeader,
+}
+
+/// Splits a byte stream into ULog messages, in whatever chunks the bytes arrive.
+///
+/// Incomplete messages at the end of a chunk are kept until the next call to `consume_bytes`.
+pub struct MessageSplitter {
+    header: Option<Header>,
+    expect_header: bool,
+    leftover: Vec<u8>,
+}
+
+impl Default for MessageSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageSplitter {
+    /// A splitter for a complete log, starting with the header
+    pub fn new() -> Self {
+        Self {
+            header: None,
+            expect_header: true,
+            leftover: Vec::new(),
+        }
+    }
+
+    /// A splitter for messages without a preceding header
+    pub fn headerless() -> Self {
+        Self {
+            header: None,
+            expect_header: false,
+            leftover: Vec::new(),
+        }
+    }
+
+    /// The header, once it was consumed
+    pub fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Number of bytes kept from an incomplete message
+    pub fn leftover_len(&self) -> usize {
+        self.leftover.len()
+    }
+
+    /// Calls `on_message` with the type and payload of every complete message in the bytes.
+    ///
+    /// Splitting stops at the first error of `on_message`, which is returned. The bytes after
+    /// the failed message are dropped.
+    pub fn consume_bytes<E, F>(&mut self, mut buf: &[u8], on_message: &mut F) -> Result<(), E>
+    where
+        E: From<FramingError>,
+        F: FnMut(u8, &[u8]) -> Result<(), E>,
+    {
+        // Complete the pending entry with only as many bytes as it needs, instead of copying buf.
+        while !self.leftover.is_empty() && !buf.is_empty() {
+            let missing = self.entry_len(&self.leftover) - self.leftover.len();
+            let num_bytes = core::cmp::min(missing, buf.len());
+            self.leftover.extend_from_slice(&buf[..num_bytes]);
+            buf = &buf[num_bytes..];
+            if self.leftover.len() == self.entry_len(&self.leftover) {
+                let entry = core::mem::take(&mut self.leftover);
+                self.split(&entry, on_message)?;
+                // Keep the allocation for the next incomplete entry
+                self.leftover = entry;
+                self.leftover.clear();
+            }
+        }
+        if self.leftover.is_empty() {
+            let consumed = self.split(buf, on_message)?;
+            self.leftover.extend_from_slice(&buf[consumed..]);
+        }
+        Ok(())
+    }
+
+    /// Drops the bytes kept from an incomplete message, e.g. when the rest of it was lost.
+    /// Splitting continues with the next bytes as the start of a message.
+    pub fn discard_leftover(&mut self) {
+        self.leftover.clear();
+    }
+
+    // The length of the entry starting with bytes, as far as it is known from them
+    fn entry_len(&self, bytes: &[u8]) -> usize {
+        if self.expect_header {
+            HEADER_LEN
+        } else if bytes.len() < 3 {
+            3
+        } else {
+            3 + u16::parse(&bytes[0..2]) as usize
+        }
+    }
+
+    // Returns the number of bytes of complete entries.
+    fn split<E, F>(&mut self, buf: &[u8], on_message: &mut F) -> Result<usize, E>
+    where
+        E: From<FramingError>,
+        F: FnMut(u8, &[u8]) -> Result<(), E>,
+    {
+        let mut consumed = 0;
+        if self.expect_header {
+            if buf.len() < HEADER_LEN {
+                return Ok(0);
+            }
+            if buf[0..7] != ULOG_MAGIC {
+                return Err(FramingError::InvalidHeader.into());
+            }
+            self.header = Some(Header {
+                version: buf[7],
+                timestamp: u64::parse(&buf[8..HEADER_LEN]),
+            });
+            self.expect_header = false;
+            consumed = HEADER_LEN;
+        }
+        loop {
+            let rest = &buf[consumed..];
+            if rest.len() < 3 {
+                return Ok(consumed);
+            }
+            let msg_size = u16::parse(&rest[0..2]) as usize;
+            if rest.len() < msg_size + 3 {
+                return Ok(consumed);
+            }
+            on_message(rest[2], &rest[3..(3 + msg_size)])?;
+            consumed += msg_size + 3;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_format;
+    use alloc::string::String;
+    use alloc::vec;
+
+    fn sample_log() -> Vec<u8> {
+        log_bytes(&[(b'B', &[0u8; 40]), (b'F', b"t:uint64_t x;")])
+    }
+
+    fn log_bytes(messages: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut bytes = ULOG_MAGIC.to_vec();
+        bytes.push(1);
+        bytes.extend_from_slice(&1234u64.to_le_bytes());
+        for (msg_type, payload) in messages.iter() {
+            bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            bytes.push(*msg_type);
+            bytes.extend_from_slice(payload);
+        }
+        bytes
+    }
+
+    #[test]
+    fn splits_in_memory_buffer() {
+        let bytes = sample_log();
+        for &chunk_size in &[1, 7, bytes.len()] {
+            let mut splitter = MessageSplitter::new();
+            let mut messages = Vec::new();
+            for chunk in bytes.chunks(chunk_size) {
+                splitter
+                    .consume_bytes(chunk, &mut |msg_type, payload| {
+                        messages.push((msg_type, payload.len()));
+                        Ok::<_, FramingError>(())
+                    })
+                    .unwrap();
+            }
+            assert_eq!(
+                splitter.header(),
+                Some(Header {
+                    version: 1,
+                    timestamp: 1234
+                })
+            );
+            assert_eq!(messages, vec![(b'B', 40), (b'F', 13)]);
+            assert_eq!(splitter.leftover_len(), 0);
+        }
+    }
+
+    #[test]
+    fn parses_format_and_decodes_field() {
+        let mut data = 1u16.to_le_bytes().to_vec();
+        data.extend_from_slice(&5_000_000u64.to_le_bytes());
+        data.extend_from_slice(&(-2.5f32).to_le_bytes());
+        data.push(3);
+        let bytes = log_bytes(&[
+            (b'F', b"sensor:uint64_t timestamp;float x;uint8_t n;"),
+            (b'D', &data),
+        ]);
+
+        let mut formats = Vec::new();
+        let mut values = Vec::new();
+        MessageSplitter::new()
+            .consume_bytes(&bytes, &mut |msg_type, payload| {
+                match msg_type {
+                    b'F' => formats.push(String::from_utf8(payload.to_vec()).unwrap()),
+                    b'D' => {
+                        let format = parse_format(&formats[0]).unwrap();
+                        let fields = &payload[2..];
+                        let x = format.field_offset("x").unwrap();
+                        let n = format.field_offset("n").unwrap();
+                        values.push((
+                            u64::parse(fields),
+                            f32::parse(&fields[x..]),
+                            u8::parse(&fields[n..]),
+                        ));
+                    }
+                    _ => (),
+                }
+                Ok::<_, FramingError>(())
+            })
+            .unwrap();
+        assert_eq!(values, vec![(5_000_000, -2.5, 3)]);
+    }
+
+    #[test]
+    fn stops_at_message_error() {
+        let mut splitter = MessageSplitter::new();
+        let mut num_messages = 0;
+        let result = splitter.consume_bytes(&sample_log(), &mut |_, _| {
+            num_messages += 1;
+            Err(FramingError::InvalidHeader)
+        });
+        assert_eq!(result, Err(FramingError::InvalidHeader));
+        assert_eq!(num_messages, 1);
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        let mut splitter = MessageSplitter::new();
+        let result = splitter.consume_bytes(&[0; 16], &mut |_, _| Ok(()));
+        assert_eq!(result, Err(FramingError::InvalidHeader));
+    }
+}
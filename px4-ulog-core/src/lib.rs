@@ -0,0 +1,23 @@
+//! The pure byte-parsing parts of px4-ulog, usable without std.
+//!
+//! Only `alloc` is required. It covers splitting a buffer into messages, parsing format
+//! definitions and decoding primitive fields. Flattening nested message types, the typed data
+//! model, file IO and the higher level parsers live in the `px4-ulog` crate.
+#![no_std]
+
+extern crate alloc;
+extern crate byteorder;
+
+mod format;
+mod framing;
+mod little_endian;
+
+pub use self::format::{parse_format, primitive_size, FormatError, FormatField, MessageFormat};
+pub use self::framing::{FramingError, Header, MessageSplitter};
+pub use self::little_endian::LittleEndianParser;
+
+/// The magic bytes every ULog file starts with, followed by the version byte
+pub const ULOG_MAGIC: [u8; 7] = [85, 76, 111, 103, 1, 18, 53];
+
+/// Size of the header: magic, version and start timestamp
+pub const HEADER_LEN: usize = 16;
@@ -0,0 +1,71 @@
+use byteorder::ByteOrder;
+
+/// Decodes a value from its little endian representation at the start of the slice
+pub trait LittleEndianParser: Sized {
+    /// Number of bytes `parse` reads
+    const SIZE: usize = core::mem::size_of::<Self>();
+
+    fn parse(serialized: &[u8]) -> Self;
+}
+impl LittleEndianParser for i8 {
+    fn parse(serialized: &[u8]) -> Self {
+        serialized[0] as i8
+    }
+}
+impl LittleEndianParser for u8 {
+    fn parse(serialized: &[u8]) -> Self {
+        serialized[0]
+    }
+}
+impl LittleEndianParser for i16 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_i16(serialized)
+    }
+}
+impl LittleEndianParser for u16 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_u16(serialized)
+    }
+}
+impl LittleEndianParser for i32 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_i32(serialized)
+    }
+}
+impl LittleEndianParser for u32 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_u32(serialized)
+    }
+}
+impl LittleEndianParser for i64 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_i64(serialized)
+    }
+}
+impl LittleEndianParser for u64 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_u64(serialized)
+    }
+}
+impl LittleEndianParser for f32 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_f32(serialized)
+    }
+}
+impl LittleEndianParser for f64 {
+    fn parse(serialized: &[u8]) -> Self {
+        byteorder::LittleEndian::read_f64(serialized)
+    }
+}
+impl LittleEndianParser for char {
+    const SIZE: usize = 1;
+
+    fn parse(serialized: &[u8]) -> Self {
+        serialized[0] as char
+    }
+}
+impl LittleEndianParser for bool {
+    fn parse(serialized: &[u8]) -> Self {
+        serialized[0] != 0
+    }
+}
@@ -0,0 +1,49 @@
+/// The 64 bit FNV-1a hash. The algorithm is fixed, so its values are stable across runs and
+/// builds, unlike those of `std::collections::hash_map::DefaultHasher`.
+pub(crate) struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub(crate) fn new() -> Self {
+        Fnv1a {
+            hash: Self::OFFSET_BASIS,
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Adds the string followed by a 0 byte, which separates consecutive strings.
+    pub(crate) fn update_str(&mut self, s: &str) {
+        self.update(s.as_bytes());
+        self.update(&[0]);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_values() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, Fnv1a::new().finish());
+        let mut hasher = Fnv1a::new();
+        hasher.update(b"a");
+        assert_eq!(0xaf63_dc4c_8601_ec8c, hasher.finish());
+        let mut hasher = Fnv1a::new();
+        hasher.update(b"foobar");
+        assert_eq!(0x8594_4171_f739_67e8, hasher.finish());
+    }
+}
@@ -0,0 +1,86 @@
+use super::{MultiId, ParsedData, SomeVec};
+use polars::prelude::*;
+
+/// Converts the samples of a message instance into a polars `DataFrame` with one column per
+/// flattened field. The `timestamp` column comes first, the others follow in the field order of
+/// the message format. `char` values become one-character strings.
+pub fn to_dataframe(
+    parsed_data: &ParsedData,
+    message_name: &str,
+    multi_id: MultiId,
+) -> PolarsResult<DataFrame> {
+    let fields = parsed_data
+        .messages
+        .get(message_name)
+        .and_then(|instances| instances.get(&multi_id))
+        .ok_or_else(|| {
+            PolarsError::NoData(
+                format!("no data for {} instance {}", message_name, multi_id.value()).into(),
+            )
+        })?;
+    let mut field_names: Vec<&str> = match parsed_data.formats.get(message_name) {
+        Some(format) => format
+            .field_iter()
+            .map(|field| field.flattened_field_name.as_str())
+            .filter(|name| fields.contains_key(*name))
+            .collect(),
+        None => fields.keys().map(String::as_str).collect(),
+    };
+    if let Some(index) = field_names.iter().position(|name| *name == "timestamp") {
+        let timestamp = field_names.remove(index);
+        field_names.insert(0, timestamp);
+    }
+    let columns = field_names
+        .into_iter()
+        .map(|name| to_series(name, &fields[name]).into_column())
+        .collect();
+    DataFrame::new_infer_height(columns)
+}
+
+fn to_series(name: &str, values: &SomeVec) -> Series {
+    let name = PlSmallStr::from(name);
+    match values {
+        SomeVec::Int8(v) => Series::new(name, v),
+        SomeVec::UInt8(v) => Series::new(name, v),
+        SomeVec::Int16(v) => Series::new(name, v),
+        SomeVec::UInt16(v) => Series::new(name, v),
+        SomeVec::Int32(v) => Series::new(name, v),
+        SomeVec::UInt32(v) => Series::new(name, v),
+        SomeVec::Int64(v) => Series::new(name, v),
+        SomeVec::UInt64(v) => Series::new(name, v),
+        SomeVec::Float(v) => Series::new(name, v),
+        SomeVec::Double(v) => Series::new(name, v),
+        SomeVec::Bool(v) => Series::new(name, v),
+        SomeVec::Char(v) => Series::new(
+            name,
+            v.iter().map(|c| c.to_string()).collect::<Vec<String>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_dataframe() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = super::super::read_file(&filename).unwrap();
+        let frame = to_dataframe(&parsed_data, "vehicle_gps_position", MultiId::new(0)).unwrap();
+        let format = &parsed_data.formats["vehicle_gps_position"];
+        assert_eq!((260, format.fields.len()), frame.shape());
+        let names = frame.get_column_names();
+        assert_eq!("timestamp", names[0].as_str());
+        assert!(names.iter().any(|name| name.as_str() == "lat"));
+        assert_eq!(
+            &DataType::UInt64,
+            frame.column("timestamp").unwrap().dtype()
+        );
+        assert_eq!(&DataType::Int32, frame.column("lat").unwrap().dtype());
+
+        assert!(to_dataframe(&parsed_data, "no_such_message", MultiId::new(0)).is_err());
+    }
+}
@@ -1,70 +1,1298 @@
+use crate::fnv1a::Fnv1a;
+use crate::stream_parser::file_reader::{DataFormat, SimpleCallbackResult};
 use crate::stream_parser::model::DataMessage;
-use crate::stream_parser::model::FlattenedField;
-use crate::stream_parser::model::FlattenedFieldValue;
-pub use crate::stream_parser::model::{FlattenedFieldType, MultiId};
-use crate::stream_parser::LittleEndianParser;
+pub use crate::stream_parser::model::FlattenedFieldValue;
+use crate::stream_parser::model::{
+    DefaultParameterMessage, DropoutMessage, InfoMessage, LogStage, LoggedStringMessage,
+    MessageType, MultipleInfoMessage, ParameterMessage,
+};
+pub use crate::stream_parser::model::{
+    DefaultType, FlattenedFieldType, FlattenedFormat, MsgId, MultiId,
+};
 use crate::stream_parser::LogParser;
-use std::collections::HashMap;
+pub use crate::stream_parser::OwnedDataMessage;
+#[cfg(feature = "rayon")]
+use crate::stream_parser::SchemaCache;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
+use std::sync::Arc;
+
+// Creates a LogParser named $parser whose callbacks feed the LogCollector in the RefCell $collector.
+macro_rules! collecting_parser {
+    ($collector:ident, $parser:ident) => {
+        let mut raw_callback = |msg_type: MessageType, data: &[u8]| {
+            $collector.borrow_mut().stats.add_message(msg_type, data);
+        };
+        let mut data_callback = |msg: &DataMessage| {
+            $collector.borrow_mut().add_data(msg);
+        };
+        let mut parameter_callback = |msg: &ParameterMessage| {
+            $collector.borrow_mut().add_parameter(msg);
+        };
+        let mut default_parameter_callback = |msg: &DefaultParameterMessage| {
+            $collector.borrow_mut().add_default_parameter(msg);
+        };
+        let mut info_callback = |msg: &InfoMessage| {
+            $collector.borrow_mut().add_info(msg);
+        };
+        let mut multiple_info_callback = |msg: &MultipleInfoMessage| {
+            $collector.borrow_mut().add_multiple_info(msg);
+        };
+        let mut logged_string_callback = |msg: &LoggedStringMessage| {
+            $collector.borrow_mut().add_logged_string(msg);
+        };
+        let mut dropout_callback = |msg: &DropoutMessage| {
+            $collector.borrow_mut().add_dropout(msg);
+        };
+        let mut $parser = LogParser::default();
+        $parser.set_keep_timestamp_resets(true);
+        $parser.set_capture_definition_bytes(true);
+        $parser.set_raw_message_callback(&mut raw_callback);
+        $parser.set_data_message_callback(&mut data_callback);
+        $parser.set_parameter_message_callback(&mut parameter_callback);
+        $parser.set_default_parameter_message_callback(&mut default_parameter_callback);
+        $parser.set_info_message_callback(&mut info_callback);
+        $parser.set_multiple_info_message_callback(&mut multiple_info_callback);
+        $parser.set_logged_string_message_callback(&mut logged_string_callback);
+        $parser.set_dropout_message_callback(&mut dropout_callback);
+    };
+}
+
+// Passes the little endian bytes of every value of the SomeVec to $add.
+macro_rules! vec_bytes_matcher {
+    ($self_i:ident, $add:ident, $( $type:tt ),*) => (
+        match $self_i {
+            $(SomeVec::$type(vec) => {
+                for value in vec {
+                    $add(&value.to_le_bytes());
+                }
+            },)+
+            SomeVec::Bool(vec) => {
+                for value in vec {
+                    $add(&[*value as u8]);
+                }
+            }
+            SomeVec::Char(vec) => {
+                for value in vec {
+                    $add(&[*value as u8]);
+                }
+            }
+        }
+    )
+}
+
+#[cfg(feature = "polars")]
+mod dataframe;
+mod rates;
+mod schema;
+mod sink;
+mod streamed;
+mod summary;
+mod validation;
+mod writer;
+
+#[cfg(feature = "polars")]
+pub use self::dataframe::to_dataframe;
+pub use self::rates::{TopicRate, TopicRateTracker};
+pub use self::schema::{schema_diff, MessageSchemaDiff, SchemaDiff};
+pub use self::sink::{read_file_into_sink, ColumnSink, FieldId};
+pub use self::streamed::{read_streamed, NO_MESSAGE_START};
+pub use self::summary::{message_type_histogram, summarize, LogSummary};
+pub use self::validation::{validate, ValidationIssue, ValidationReport};
+pub use self::writer::{write_ulog_subset, UlogWriter};
 
 pub struct ParsedData {
     pub messages: HashMap<String, HashMap<MultiId, HashMap<String, SomeVec>>>,
+    /// The flattened format of every message defined in the log, logged or not.
+    pub formats: HashMap<String, FlattenedFormat>,
+    /// The ULog file format version
+    pub version: u8,
+    /// The logging start time in microseconds
+    pub start_timestamp: u64,
+    /// Parameter values at the start of logging
+    pub parameters: HashMap<String, ParamValue>,
+    /// Parameters changed while logging, in the order of the log. Parameter messages have no
+    /// timestamp, so the latest timestamp of any data message before the change is used.
+    pub changed_parameters: Vec<(u64, String, ParamValue)>,
+    /// System wide default values of the parameters, for logs which contain them
+    pub default_parameters: HashMap<String, ParamValue>,
+    /// Default values of the parameters for the current configuration, e.g. the airframe, for
+    /// logs which contain them
+    pub airframe_default_parameters: HashMap<String, ParamValue>,
+    pub info: HashMap<String, InfoValue>,
+    /// The values of multiple info messages per key, in the order of the log, with continued
+    /// chunks appended to the value they continue
+    pub multiple_info: HashMap<String, Vec<Vec<u8>>>,
+    pub logged_strings: Vec<LoggedString>,
+    pub dropouts: Vec<Dropout>,
+    /// Message instances with more samples than the cap of `read_file_capped`, only the
+    /// earliest samples are contained in `messages` for those
+    pub capped_messages: HashSet<(String, MultiId)>,
+    /// The raw header and all messages before the first data message, to write filtered data
+    /// with the original definitions
+    pub definition_bytes: Vec<u8>,
+}
+
+impl ParsedData {
+    /// The timestamps of all samples of a message instance, in microseconds.
+    ///
+    /// Returns None if the instance was not logged or its timestamp is not a `uint64_t`.
+    pub fn timestamps(&self, message_name: &str, multi_id: MultiId) -> Option<&[u64]> {
+        match self
+            .messages
+            .get(message_name)?
+            .get(&multi_id)?
+            .get("timestamp")?
+        {
+            SomeVec::UInt64(timestamps) => Some(timestamps),
+            _ => None,
+        }
+    }
+
+    /// The names of the fields of a message instance with more than one distinct value, sorted,
+    /// e.g. to skip constant columns when plotting. See `SomeVec::varies`.
+    pub fn varying_fields(&self, message_name: &str, multi_id: MultiId) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .messages
+            .get(message_name)
+            .and_then(|instances| instances.get(&multi_id))
+            .into_iter()
+            .flatten()
+            .filter(|(_, values)| values.varies())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The column of a field for every logged instance of a message, e.g. of several batteries.
+    ///
+    /// Returns an empty map if the message was not logged or has no such field.
+    pub fn field_all_instances(
+        &self,
+        message_name: &str,
+        field_name: &str,
+    ) -> HashMap<MultiId, &SomeVec> {
+        self.messages
+            .get(message_name)
+            .into_iter()
+            .flatten()
+            .filter_map(|(multi_id, fields)| Some((multi_id.clone(), fields.get(field_name)?)))
+            .collect()
+    }
+
+    /// Removes the data of all instances of a message and returns it, e.g. to move each topic
+    /// to a different worker thread without cloning. Its format stays in `formats`.
+    ///
+    /// Returns None if the message was not logged or was already taken.
+    pub fn take_topic(
+        &mut self,
+        message_name: &str,
+    ) -> Option<HashMap<MultiId, HashMap<String, SomeVec>>> {
+        self.messages.remove(message_name)
+    }
+
+    /// A fingerprint of the logged data, e.g. for golden tests. It covers the message names,
+    /// multi_ids, field names and the bytes of every column, in sorted order. Like
+    /// `DataFormat::schema_hash` it is stable across runs and builds.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        let mut add_bytes = |bytes: &[u8]| hasher.update(bytes);
+        let mut message_names: Vec<&String> = self.messages.keys().collect();
+        message_names.sort();
+        for message_name in message_names {
+            add_bytes(message_name.as_bytes());
+            add_bytes(&[0]);
+            let instances = &self.messages[message_name];
+            let mut multi_ids: Vec<&MultiId> = instances.keys().collect();
+            multi_ids.sort();
+            for multi_id in multi_ids {
+                add_bytes(&[multi_id.value()]);
+                let fields = &instances[multi_id];
+                let mut field_names: Vec<&String> = fields.keys().collect();
+                field_names.sort();
+                for field_name in field_names {
+                    add_bytes(field_name.as_bytes());
+                    add_bytes(&[0]);
+                    let column = &fields[field_name];
+                    add_bytes(&(column.len() as u64).to_le_bytes());
+                    vec_bytes_matcher!(
+                        column, add_bytes, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64,
+                        UInt64, Float, Double
+                    );
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The index of the sample of a message instance whose timestamp is nearest to `t_us`, the
+    /// earlier one if two are equally near. Times outside of the logged range are clamped to the
+    /// first or last sample. After a timestamp reset, see `timestamp_resets`, the nearest of all
+    /// samples is taken, the first one in sample order on ties.
+    ///
+    /// Returns None if there are no samples or no timestamps, see `timestamps`.
+    pub fn index_at_time(&self, message_name: &str, multi_id: MultiId, t_us: u64) -> Option<usize> {
+        let timestamps = self.timestamps(message_name, multi_id)?;
+        nearest_index(timestamps, is_sorted(timestamps), t_us)
+    }
+
+    /// Pairs the index of every sample of the `left` message instance with the index of the
+    /// sample of the `right` one nearest in time, see `index_at_time`, or None if that is more
+    /// than `tolerance_us` away.
+    ///
+    /// Returns an empty Vec if the left instance has no timestamps.
+    pub fn join_nearest(
+        &self,
+        left: (&str, MultiId),
+        right: (&str, MultiId),
+        tolerance_us: u64,
+    ) -> Vec<(usize, Option<usize>)> {
+        let left_timestamps = self.timestamps(left.0, left.1).unwrap_or(&[]);
+        let right_timestamps = self.timestamps(right.0, right.1).unwrap_or(&[]);
+        let right_sorted = is_sorted(right_timestamps);
+        left_timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, t_us)| {
+                let nearest = nearest_index(right_timestamps, right_sorted, *t_us)
+                    .filter(|nearest| right_timestamps[*nearest].abs_diff(*t_us) <= tolerance_us);
+                (index, nearest)
+            })
+            .collect()
+    }
+
+    /// The indices of the samples of a message instance whose timestamp is smaller than the one of
+    /// the previous sample, e.g. because of a clock reset. Such data needs special care before
+    /// using the timestamps as a time axis.
+    ///
+    /// Returns an empty list if there are no timestamps, see `timestamps`.
+    pub fn timestamp_resets(&self, message_name: &str, multi_id: MultiId) -> Vec<usize> {
+        let timestamps = self.timestamps(message_name, multi_id).unwrap_or(&[]);
+        timestamps
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[1] < pair[0])
+            .map(|(index, _)| index + 1)
+            .collect()
+    }
+
+    /// The index, value and timestamp of the first sample with the largest value of a numeric
+    /// field, e.g. of the peak current. NaN values are skipped.
+    ///
+    /// Returns None if the field is not numeric, has no values other than NaN, or if the message
+    /// instance has no timestamps.
+    pub fn argmax(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+        field_name: &str,
+    ) -> Option<(usize, f64, u64)> {
+        self.arg_extremum(message_name, multi_id, field_name, |value, best| {
+            value > best
+        })
+    }
+
+    /// The index, value and timestamp of the first sample with the smallest value of a numeric
+    /// field, see `argmax`.
+    pub fn argmin(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+        field_name: &str,
+    ) -> Option<(usize, f64, u64)> {
+        self.arg_extremum(message_name, multi_id, field_name, |value, best| {
+            value < best
+        })
+    }
+
+    fn arg_extremum(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+        field_name: &str,
+        is_better: fn(f64, f64) -> bool,
+    ) -> Option<(usize, f64, u64)> {
+        let timestamps = self.timestamps(message_name, multi_id.clone())?;
+        let values = self
+            .messages
+            .get(message_name)?
+            .get(&multi_id)?
+            .get(field_name)?
+            .iter_f64(None)?;
+        let (index, value) = values
+            .enumerate()
+            .filter(|(_, value)| !value.is_nan())
+            .fold(
+                None,
+                |best: Option<(usize, f64)>, (index, value)| match best {
+                    Some((_, best_value)) if !is_better(value, best_value) => best,
+                    _ => Some((index, value)),
+                },
+            )?;
+        Some((index, value, *timestamps.get(index)?))
+    }
+
+    /// The number of samples of a message instance divided by the number expected from its median
+    /// sampling period over the time span from the smallest to the largest timestamp. Values below
+    /// 1.0 indicate lost samples.
+    ///
+    /// Returns None if there are no timestamps or not enough distinct ones to get a period.
+    pub fn completeness(&self, message_name: &str, multi_id: MultiId) -> Option<f64> {
+        let timestamps = self.timestamps(message_name, multi_id)?;
+        let mut periods: Vec<u64> = timestamps
+            .windows(2)
+            .map(|pair| pair[1].saturating_sub(pair[0]))
+            .collect();
+        if periods.is_empty() {
+            return None;
+        }
+        periods.sort_unstable();
+        let median_period = periods[periods.len() / 2];
+        if median_period == 0 {
+            return None;
+        }
+        let duration = timestamps.iter().max()? - timestamps.iter().min()?;
+        let expected_samples = (duration as f64 / median_period as f64).round() + 1.0;
+        Some(timestamps.len() as f64 / expected_samples)
+    }
+
+    /// The last `n` samples of a message instance as rows of field name to value, oldest first.
+    /// All samples are returned if there are fewer than `n`.
+    pub fn tail(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+        n: usize,
+    ) -> Vec<HashMap<String, FlattenedFieldValue>> {
+        let fields = match self
+            .messages
+            .get(message_name)
+            .and_then(|instances| instances.get(&multi_id))
+        {
+            Some(fields) => fields,
+            None => return Vec::new(),
+        };
+        let num_samples = fields.values().map(SomeVec::len).min().unwrap_or(0);
+        (num_samples.saturating_sub(n)..num_samples)
+            .map(|index| row(fields, index))
+            .collect()
+    }
+
+    /// All samples of a message instance as `(timestamp, row)`, sorted by timestamp.
+    ///
+    /// Returns an empty Vec if the instance was not logged or has no `uint64_t` timestamp.
+    pub fn timed_rows(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+    ) -> Vec<(u64, HashMap<String, FlattenedFieldValue>)> {
+        let timestamps = match self.timestamps(message_name, multi_id.clone()) {
+            Some(timestamps) => timestamps,
+            None => return Vec::new(),
+        };
+        let fields = &self.messages[message_name][&multi_id];
+        let mut rows: Vec<_> = timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, timestamp)| (*timestamp, row(fields, index)))
+            .collect();
+        // Stable, so samples with equal timestamps stay in the order of the log
+        rows.sort_by_key(|(timestamp, _)| *timestamp);
+        rows
+    }
+
+    /// The values of a parameter over time: the value at the start of logging with the start
+    /// timestamp, followed by every change while logging, see `changed_parameters`.
+    pub fn parameter_history(&self, name: &str) -> Vec<(u64, ParamValue)> {
+        let initial_value = self
+            .parameters
+            .get(name)
+            .map(|value| (self.start_timestamp, *value));
+        initial_value
+            .into_iter()
+            .chain(
+                self.changed_parameters
+                    .iter()
+                    .filter(|(_, changed_name, _)| changed_name == name)
+                    .map(|(timestamp, _, value)| (*timestamp, *value)),
+            )
+            .collect()
+    }
+
+    /// The changes of a parameter as `(timestamp, old value, new value)`, see
+    /// `parameter_history`. Changes that keep the value are included too.
+    pub fn parameter_changes(&self, name: &str) -> Vec<(u64, ParamValue, ParamValue)> {
+        self.parameter_history(name)
+            .windows(2)
+            .map(|pair| (pair[1].0, pair[0].1, pair[1].1))
+            .collect()
+    }
+
+    /// The default parameter values of one kind, see `default_parameters` and
+    /// `airframe_default_parameters`.
+    pub fn default_parameters_of(&self, default_type: DefaultType) -> &HashMap<String, ParamValue> {
+        match default_type {
+            DefaultType::System => &self.default_parameters,
+            DefaultType::Airframe => &self.airframe_default_parameters,
+        }
+    }
+
+    /// The complete parameter set at the end of logging: the system wide defaults, overridden by
+    /// the defaults of the current configuration, overridden by the logged values including
+    /// their changes while logging.
+    pub fn effective_parameters(&self) -> HashMap<String, ParamValue> {
+        let mut parameters = self.default_parameters.clone();
+        parameters.extend(
+            self.airframe_default_parameters
+                .iter()
+                .chain(self.parameters.iter())
+                .map(|(name, value)| (name.clone(), *value)),
+        );
+        for (_, name, value) in &self.changed_parameters {
+            parameters.insert(name.clone(), *value);
+        }
+        parameters
+    }
+
+    /// The parameters whose value at the end of logging differs from the system wide default, as
+    /// `(name, value, default value)` sorted by name. Parameters without a default are skipped.
+    pub fn non_default_parameters(&self) -> Vec<(String, ParamValue, ParamValue)> {
+        let mut non_default: Vec<_> = self
+            .default_parameters
+            .iter()
+            .filter_map(|(name, default)| {
+                let (_, value) = *self.parameter_history(name).last()?;
+                if value == *default {
+                    return None;
+                }
+                Some((name.clone(), value, *default))
+            })
+            .collect();
+        non_default.sort_by(|a, b| a.0.cmp(&b.0));
+        non_default
+    }
+
+    /// The reassembled multiple info values whose key starts with the prefix, e.g.
+    /// "hardware_version", as `(key, values)` sorted by key.
+    pub fn multiple_info_with_prefix(&self, prefix: &str) -> Vec<(&str, &[Vec<u8>])> {
+        let mut values: Vec<_> = self
+            .multiple_info
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, values)| (key.as_str(), values.as_slice()))
+            .collect();
+        values.sort_by_key(|(key, _)| *key);
+        values
+    }
+
+    /// The time windows in which the vehicle was armed as `(start, end)` timestamps, from the
+    /// `arming_state` of `vehicle_status`, see `value_intervals`.
+    ///
+    /// Only `ARMING_STATE_ARMED` counts as armed. The state after it is `STANDBY_ERROR` since PX4
+    /// v1.10, a disarmed state, but was `ARMED_ERROR` before, so such older logs may miss armed
+    /// time with errors.
+    pub fn armed_intervals(&self) -> Vec<(u64, u64)> {
+        const ARMING_STATE_ARMED: f64 = 2.0;
+        self.value_intervals("vehicle_status", MultiId::new(0), "arming_state", |state| {
+            state == ARMING_STATE_ARMED
+        })
+    }
+
+    /// The time windows in which the value of a field, widened to f64, satisfies the predicate,
+    /// as `(start, end)` timestamps. A window ends with the first sample not satisfying it, or
+    /// with the last sample if it is still open at the end of the log. The windows follow the
+    /// sample order, so after a timestamp reset, see `timestamp_resets`, a window may end before
+    /// it starts.
+    ///
+    /// Returns an empty Vec if there is no such field or the message has no `uint64_t`
+    /// timestamp.
+    pub fn value_intervals<P: Fn(f64) -> bool>(
+        &self,
+        message_name: &str,
+        multi_id: MultiId,
+        field_name: &str,
+        predicate: P,
+    ) -> Vec<(u64, u64)> {
+        let timestamps = match self.timestamps(message_name, multi_id.clone()) {
+            Some(timestamps) => timestamps,
+            None => return Vec::new(),
+        };
+        let values = match self.messages[message_name][&multi_id]
+            .get(field_name)
+            .and_then(|values| values.iter_f64(None))
+        {
+            Some(values) => values,
+            None => return Vec::new(),
+        };
+        let mut intervals = Vec::new();
+        let mut satisfied_since = None;
+        for (timestamp, value) in timestamps.iter().zip(values) {
+            let satisfied = predicate(value);
+            match satisfied_since {
+                None if satisfied => satisfied_since = Some(*timestamp),
+                Some(start) if !satisfied => {
+                    intervals.push((start, *timestamp));
+                    satisfied_since = None;
+                }
+                _ => (),
+            }
+        }
+        if let (Some(start), Some(end)) = (satisfied_since, timestamps.last()) {
+            intervals.push((start, *end));
+        }
+        intervals
+    }
+
+    /// The attitude of every `vehicle_attitude` sample as `(roll, pitch, yaw)` in radians,
+    /// converted from the quaternion `q` (w, x, y, z).
+    ///
+    /// Returns None if the instance was not logged or has no float quaternion.
+    pub fn euler_angles(&self, multi_id: MultiId) -> Option<Vec<(f32, f32, f32)>> {
+        let fields = self.messages.get("vehicle_attitude")?.get(&multi_id)?;
+        let mut components = Vec::with_capacity(4);
+        for index in 0..4 {
+            match fields.get(&format!("q[{}]", index))? {
+                SomeVec::Float(values) => components.push(values),
+                _ => return None,
+            }
+        }
+        Some(
+            (0..components[0].len())
+                .map(|i| {
+                    quaternion_to_euler(
+                        components[0][i],
+                        components[1][i],
+                        components[2][i],
+                        components[3][i],
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// The position of every `vehicle_local_position` sample as `(x, y, z)` in meters, in the
+    /// local NED frame.
+    ///
+    /// Returns None if the instance was not logged or has no float position.
+    pub fn local_trajectory(&self, multi_id: MultiId) -> Option<Vec<(f32, f32, f32)>> {
+        let fields = self
+            .messages
+            .get("vehicle_local_position")?
+            .get(&multi_id)?;
+        match (fields.get("x")?, fields.get("y")?, fields.get("z")?) {
+            (SomeVec::Float(x), SomeVec::Float(y), SomeVec::Float(z)) => Some(
+                x.iter()
+                    .zip(y.iter())
+                    .zip(z.iter())
+                    .map(|((x, y), z)| (*x, *y, *z))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The rates and accelerations of every `sensor_combined` sample, see `ImuSample`.
+    /// `sensor_combined` has a single instance.
+    ///
+    /// Returns None if it was not logged or has no float `gyro_rad` and `accelerometer_m_s2`.
+    pub fn imu_samples(&self) -> Option<Vec<ImuSample>> {
+        let fields = self
+            .messages
+            .get("sensor_combined")?
+            .get(&MultiId::new(0))?;
+        let timestamps = self.timestamps("sensor_combined", MultiId::new(0))?;
+        let vector = |name: &str| -> Option<[&Vec<f32>; 3]> {
+            let mut components = Vec::with_capacity(3);
+            for index in 0..3 {
+                match fields.get(&format!("{}[{}]", name, index))? {
+                    SomeVec::Float(values) => components.push(values),
+                    _ => return None,
+                }
+            }
+            Some([components[0], components[1], components[2]])
+        };
+        let gyro = vector("gyro_rad")?;
+        let accel = vector("accelerometer_m_s2")?;
+        Some(
+            timestamps
+                .iter()
+                .enumerate()
+                .map(|(i, timestamp)| ImuSample {
+                    timestamp: *timestamp,
+                    gyro: [gyro[0][i], gyro[1][i], gyro[2][i]],
+                    accel: [accel[0][i], accel[1][i], accel[2][i]],
+                })
+                .collect(),
+        )
+    }
+
+    /// The progress of every `mission_result` sample, e.g. to review the waypoints reached
+    /// during an autonomous mission. `mission_result` has a single instance.
+    ///
+    /// Returns an empty Vec if it was not logged.
+    pub fn mission_events(&self) -> Vec<MissionEvent> {
+        let fields = match self
+            .messages
+            .get("mission_result")
+            .and_then(|instances| instances.get(&MultiId::new(0)))
+        {
+            Some(fields) => fields,
+            None => return Vec::new(),
+        };
+        let timestamps = self
+            .timestamps("mission_result", MultiId::new(0))
+            .unwrap_or(&[]);
+        // seq_reached is signed in newer PX4 versions
+        let seq_reached: Vec<i64> = match fields.get("seq_reached").and_then(|v| v.iter_f64(None)) {
+            Some(values) => values.map(|v| v as i64).collect(),
+            None => return Vec::new(),
+        };
+        let finished: Vec<bool> = match fields.get("finished") {
+            Some(SomeVec::Bool(values)) => values.clone(),
+            Some(values) => match values.iter_f64(None) {
+                Some(values) => values.map(|v| v != 0.0).collect(),
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+        timestamps
+            .iter()
+            .zip(seq_reached)
+            .zip(finished)
+            .map(|((timestamp, seq_reached), finished)| MissionEvent {
+                timestamp: *timestamp,
+                seq_reached,
+                finished,
+            })
+            .collect()
+    }
+
+    /// The commands of all `vehicle_command` instances, sorted by timestamp, e.g. to reconstruct
+    /// the command history of a flight.
+    ///
+    /// Returns an empty Vec if no commands were logged.
+    pub fn commands(&self) -> Vec<VehicleCommand> {
+        let mut commands = Vec::new();
+        for (multi_id, fields) in self.messages.get("vehicle_command").into_iter().flatten() {
+            let timestamps = match self.timestamps("vehicle_command", multi_id.clone()) {
+                Some(timestamps) => timestamps,
+                None => continue,
+            };
+            // The types of the fields differ between PX4 versions
+            let column = |name: &str| -> Option<Vec<f64>> {
+                Some(fields.get(name)?.iter_f64(None)?.collect())
+            };
+            let command_ids = match column("command") {
+                Some(command_ids) => command_ids,
+                None => continue,
+            };
+            let mut params = Vec::with_capacity(7);
+            for index in 1..=7 {
+                match column(&format!("param{}", index)) {
+                    Some(values) => params.push(values),
+                    None => break,
+                }
+            }
+            if params.len() != 7 {
+                continue;
+            }
+            for (index, timestamp) in timestamps.iter().enumerate() {
+                let mut command_params = [0.0; 7];
+                for (param, values) in command_params.iter_mut().zip(&params) {
+                    *param = values[index];
+                }
+                commands.push(VehicleCommand {
+                    timestamp: *timestamp,
+                    command: command_ids[index] as u32,
+                    params: command_params,
+                });
+            }
+        }
+        commands.sort_by_key(|command| command.timestamp);
+        commands
+    }
+
+    /// The CPU load and RAM usage over the log from the `cpuload` topic, both as fractions of 1.
+    ///
+    /// Returns None if `cpuload` was not logged.
+    pub fn cpu_summary(&self) -> Option<CpuSummary> {
+        let fields = self.messages.get("cpuload")?.get(&MultiId::new(0))?;
+        let (loads, ram_usages) = match (fields.get("load"), fields.get("ram_usage")) {
+            (Some(SomeVec::Float(loads)), Some(SomeVec::Float(ram_usages))) => (loads, ram_usages),
+            _ => return None,
+        };
+        if loads.is_empty() {
+            return None;
+        }
+        Some(CpuSummary {
+            max_load: loads.iter().cloned().fold(f32::NAN, f32::max),
+            mean_load: loads.iter().sum::<f32>() / loads.len() as f32,
+            max_ram: ram_usages.iter().cloned().fold(f32::NAN, f32::max),
+        })
+    }
+
+    /// The lowest voltage and remaining capacity, the highest current and the number of samples
+    /// with a warning of a battery from the `battery_status` topic.
+    ///
+    /// Returns None if the battery instance was not logged.
+    pub fn battery_summary(&self, multi_id: MultiId) -> Option<BatterySummary> {
+        let fields = self.messages.get("battery_status")?.get(&multi_id)?;
+        let (voltages, remaining, currents) = match (
+            fields.get("voltage_v"),
+            fields.get("remaining"),
+            fields.get("current_a"),
+        ) {
+            (
+                Some(SomeVec::Float(voltages)),
+                Some(SomeVec::Float(remaining)),
+                Some(SomeVec::Float(currents)),
+            ) => (voltages, remaining, currents),
+            _ => return None,
+        };
+        if voltages.is_empty() {
+            return None;
+        }
+        // The type of the warning changed between PX4 versions
+        let warning_count = fields
+            .get("warning")
+            .and_then(|warnings| warnings.iter_f64(None))
+            .map_or(0, |warnings| warnings.filter(|w| *w != 0.0).count());
+        Some(BatterySummary {
+            min_voltage: voltages.iter().cloned().fold(f32::NAN, f32::min),
+            min_remaining: remaining.iter().cloned().fold(f32::NAN, f32::min),
+            max_current: currents.iter().cloned().fold(f32::NAN, f32::max),
+            warning_count,
+        })
+    }
+
+    /// The area covered by the GPS fixes of `vehicle_gps_position`, in degrees. Samples without
+    /// at least a 2D fix or at 0/0 are ignored.
+    ///
+    /// Returns None if there is no such topic or no valid fix.
+    pub fn gps_bounds(&self) -> Option<BoundingBox> {
+        let mut bounds: Option<BoundingBox> = None;
+        for fields in self.messages.get("vehicle_gps_position")?.values() {
+            let (lats, lons) = match (fields.get("lat"), fields.get("lon")) {
+                (Some(SomeVec::Int32(lats)), Some(SomeVec::Int32(lons))) => (lats, lons),
+                _ => continue,
+            };
+            let fix_types = match fields.get("fix_type") {
+                Some(SomeVec::UInt8(fix_types)) => Some(fix_types),
+                _ => None,
+            };
+            for (index, (lat, lon)) in lats.iter().zip(lons).enumerate() {
+                let has_fix = match fix_types {
+                    Some(fix_types) => fix_types[index] >= 2,
+                    None => true,
+                };
+                if !has_fix || (*lat == 0 && *lon == 0) {
+                    continue;
+                }
+                let (lat, lon) = (*lat as f64 * 1e-7, *lon as f64 * 1e-7);
+                bounds = Some(match bounds {
+                    Some(b) => BoundingBox {
+                        min_lat: b.min_lat.min(lat),
+                        max_lat: b.max_lat.max(lat),
+                        min_lon: b.min_lon.min(lon),
+                        max_lon: b.max_lon.max(lon),
+                    },
+                    None => BoundingBox {
+                        min_lat: lat,
+                        max_lat: lat,
+                        min_lon: lon,
+                        max_lon: lon,
+                    },
+                });
+            }
+        }
+        bounds
+    }
+
+    /// The logged strings with their timestamps relative to the first data sample of any topic,
+    /// e.g. to annotate a plot of the data. Strings logged before any data have a negative time.
+    ///
+    /// Returns None if the log does not contain data.
+    pub fn logged_strings_relative_to_data(&self) -> Option<Vec<(i64, &LoggedString)>> {
+        let first_timestamp = self
+            .messages
+            .values()
+            .flat_map(|instances| instances.values())
+            .filter_map(|fields| match fields.get("timestamp") {
+                Some(SomeVec::UInt64(timestamps)) => timestamps.iter().min(),
+                _ => None,
+            })
+            .min()?;
+        Some(
+            self.logged_strings
+                .iter()
+                .map(|s| (s.timestamp as i64 - *first_timestamp as i64, s))
+                .collect(),
+        )
+    }
+
+    /// The logged strings ordered by log level, the most severe first, and then by timestamp,
+    /// e.g. to triage errors. The levels are the ASCII digits '0' (emergency) to '7' (debug).
+    pub fn logged_strings_by_severity(&self) -> Vec<&LoggedString> {
+        let mut logged_strings: Vec<&LoggedString> = self.logged_strings.iter().collect();
+        logged_strings.sort_by_key(|s| (s.log_level, s.timestamp));
+        logged_strings
+    }
+
+    /// The logged strings, parameter changes and dropouts merged into one timeline, ordered by
+    /// timestamp. Events with equal timestamps keep that order.
+    pub fn events(&self) -> Vec<Event> {
+        let mut events: Vec<Event> = self
+            .logged_strings
+            .iter()
+            .cloned()
+            .map(Event::LogLine)
+            .chain(
+                self.changed_parameters
+                    .iter()
+                    .map(|(timestamp, name, value)| Event::ParameterChange {
+                        timestamp: *timestamp,
+                        name: name.clone(),
+                        value: *value,
+                    }),
+            )
+            .chain(self.dropouts.iter().cloned().map(Event::Dropout))
+            .collect();
+        events.sort_by_key(Event::timestamp);
+        events
+    }
+
+    /// A one line overview for logging and CLI output, e.g.
+    /// `v1 log, 120.3 s, 87 topics, 1.2M samples, 3 dropouts`.
+    ///
+    /// The duration is the time between the smallest and the largest data sample timestamp of any
+    /// topic.
+    pub fn summary_line(&self) -> String {
+        let mut time_range: Option<(u64, u64)> = None;
+        let mut sample_count = 0;
+        for fields in self
+            .messages
+            .values()
+            .flat_map(|instances| instances.values())
+        {
+            sample_count += fields.values().next().map_or(0, SomeVec::len);
+            if let Some(SomeVec::UInt64(timestamps)) = fields.get("timestamp") {
+                if let (Some(min), Some(max)) = (timestamps.iter().min(), timestamps.iter().max()) {
+                    time_range = Some(match time_range {
+                        Some((start, end)) => (start.min(*min), end.max(*max)),
+                        None => (*min, *max),
+                    });
+                }
+            }
+        }
+        let duration_s = time_range.map_or(0.0, |(start, end)| (end - start) as f64 / 1e6);
+        let samples = if sample_count >= 1_000_000 {
+            format!("{:.1}M", sample_count as f64 / 1e6)
+        } else if sample_count >= 1_000 {
+            format!("{:.1}k", sample_count as f64 / 1e3)
+        } else {
+            sample_count.to_string()
+        };
+        format!(
+            "v{} log, {:.1} s, {} topics, {} samples, {} dropouts",
+            self.version,
+            duration_s,
+            self.messages.len(),
+            samples,
+            self.dropouts.len()
+        )
+    }
+}
+
+/// An entry of the timeline of a log, see `ParsedData::events`
+#[derive(Clone, Debug)]
+pub enum Event {
+    LogLine(LoggedString),
+    ParameterChange {
+        timestamp: u64,
+        name: String,
+        value: ParamValue,
+    },
+    Dropout(Dropout),
+}
+
+impl Event {
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Event::LogLine(logged_string) => logged_string.timestamp,
+            Event::ParameterChange { timestamp, .. } => *timestamp,
+            Event::Dropout(dropout) => dropout.timestamp,
+        }
+    }
+}
+
+/// A sample of `sensor_combined`, see `ParsedData::imu_samples`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImuSample {
+    pub timestamp: u64,
+    /// Angular rates around the body axes in rad/s
+    pub gyro: [f32; 3],
+    /// Accelerations along the body axes in m/s^2
+    pub accel: [f32; 3],
+}
+
+/// A sample of `mission_result`, see `ParsedData::mission_events`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MissionEvent {
+    pub timestamp: u64,
+    /// The index of the last mission item reached, -1 if none was reached yet
+    pub seq_reached: i64,
+    /// Whether the mission was completed
+    pub finished: bool,
+}
+
+/// A sample of `vehicle_command`, see `ParsedData::commands`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VehicleCommand {
+    pub timestamp: u64,
+    /// The MAVLink command id, e.g. 400 for arming
+    pub command: u32,
+    /// `param1` to `param7`. Newer PX4 versions log `param5` and `param6` as doubles, so all
+    /// parameters are widened to f64.
+    pub params: [f64; 7],
+}
+
+/// See `ParsedData::cpu_summary`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuSummary {
+    pub max_load: f32,
+    pub mean_load: f32,
+    pub max_ram: f32,
+}
+
+/// See `ParsedData::battery_summary`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatterySummary {
+    /// In volts
+    pub min_voltage: f32,
+    /// As a fraction of 1
+    pub min_remaining: f32,
+    /// In amperes
+    pub max_current: f32,
+    pub warning_count: usize,
+}
+
+/// A latitude/longitude range in degrees, see `ParsedData::gps_bounds`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamValue {
+    Int32(i32),
+    Float(f32),
+}
+
+#[derive(Clone, Debug)]
+pub enum InfoValue {
+    String(String),
+    Value(FlattenedFieldValue),
+    /// Any type which is not a char array or a single value
+    Other {
+        value_type: String,
+        bytes: Vec<u8>,
+    },
+}
+
+impl InfoValue {
+    fn from_message(msg: &InfoMessage) -> Self {
+        if msg.value_type.starts_with("char[") {
+            let text = String::from_utf8_lossy(msg.value);
+            return InfoValue::String(text.trim_end_matches('\0').to_string());
+        }
+        match FlattenedFieldType::from_type_name(msg.value_type) {
+            Some(field_type) if msg.value.len() == field_type.size() as usize => {
+                InfoValue::Value(field_type.decode_value(msg.value))
+            }
+            _ => InfoValue::Other {
+                value_type: msg.value_type.to_string(),
+                bytes: msg.value.to_vec(),
+            },
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            InfoValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoggedString {
+    pub log_level: u8,
+    pub timestamp: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Dropout {
+    pub duration_ms: u16,
+    /// Dropout messages have no timestamp, so the latest timestamp of any data message before
+    /// the dropout is used.
+    pub timestamp: u64,
+}
+
+/// Counts gathered while reading a log, see `read_file_with_stats`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseStats {
+    /// Number of messages, not counting the header
+    pub messages_parsed: usize,
+    /// Bytes of the header and all messages
+    pub bytes_consumed: usize,
+    /// Number of format definitions, including nested ones which are never logged
+    pub formats_defined: usize,
+    /// Number of AddLoggedMessage messages
+    pub subscriptions: usize,
+}
+
+impl ParseStats {
+    fn add_message(&mut self, msg_type: MessageType, data: &[u8]) {
+        self.messages_parsed += 1;
+        self.bytes_consumed += 3 + data.len();
+        match msg_type {
+            MessageType::Format => self.formats_defined += 1,
+            MessageType::AddLoggedMessage => self.subscriptions += 1,
+            _ => (),
+        }
+    }
 }
 
 pub fn read_file(file_path: &str) -> Result<ParsedData, std::io::Error> {
-    let mut f = std::fs::File::open(file_path)?;
+    read_from_reader(std::fs::File::open(file_path)?)
+}
+
+/// Reads a log like `read_file`, and also returns counts about the log, e.g. to monitor ingestion.
+pub fn read_file_with_stats(file_path: &str) -> Result<(ParsedData, ParseStats), std::io::Error> {
+    let collector = RefCell::new(LogCollector::default());
+    collecting_parser!(collector, parser);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+    let mut stats = collector.borrow().stats.clone();
+    stats.bytes_consumed += px4_ulog_core::HEADER_LEN;
+    let parsed_data = collector.take().into_parsed_data(parser)?;
+    Ok((parsed_data, stats))
+}
+
+/// Reads a log like `read_file`, but keeps at most the first `max_samples_per_topic` samples of
+/// every message instance to bound the memory use, e.g. for untrusted logs.
+///
+/// The instances which had samples dropped are listed in `ParsedData::capped_messages`.
+pub fn read_file_capped(
+    file_path: &str,
+    max_samples_per_topic: usize,
+) -> Result<ParsedData, std::io::Error> {
+    let mut collector = LogCollector::default();
+    collector.reader.max_samples = Some(max_samples_per_topic);
+    collect_from_reader(std::fs::File::open(file_path)?, collector)
+}
+
+/// Reads several logs in parallel. The flattened formats are shared between logs with the same
+/// definitions, e.g. from the same firmware, so identical definitions are flattened only once.
+#[cfg(feature = "rayon")]
+pub fn read_files(file_paths: &[&str]) -> Vec<Result<ParsedData, std::io::Error>> {
+    use rayon::prelude::*;
+
+    let schema_cache = SchemaCache::new();
+    file_paths
+        .par_iter()
+        .map(|file_path| {
+            let collector = RefCell::new(LogCollector::default());
+            collecting_parser!(collector, parser);
+            parser.set_schema_cache(&schema_cache);
+            consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+            collector.take().into_parsed_data(parser)
+        })
+        .collect()
+}
+
+/// Reads a log like `read_file` from any `Read`, e.g. a reader which fetches the bytes of a
+/// remote file lazily.
+///
+/// The log is read once from start to end in chunks of up to 960 KiB, without seeking. Reads
+/// may return fewer bytes than requested, the parser continues where the previous chunk ended.
+pub fn read_from_reader<R: Read>(f: R) -> Result<ParsedData, std::io::Error> {
+    collect_from_reader(f, LogCollector::default())
+}
+
+fn collect_from_reader<R: Read>(
+    f: R,
+    collector: LogCollector,
+) -> Result<ParsedData, std::io::Error> {
+    collect_with(collector, |parser| consume_reader(f, parser))
+}
+
+// Collects the log from the bytes which feed passes to the parser.
+fn collect_with<F: FnOnce(&mut LogParser) -> Result<(), std::io::Error>>(
+    collector: LogCollector,
+    feed: F,
+) -> Result<ParsedData, std::io::Error> {
+    let collector = RefCell::new(collector);
+    collecting_parser!(collector, parser);
+    feed(&mut parser)?;
+    collector.take().into_parsed_data(parser)
+}
+
+/// Calls `c` for every data message of the given message instance, without storing any data.
+///
+/// This is the most memory efficient way to read a topic, the values can be decoded inside the
+/// callback e.g. with a `FieldParser`.
+pub fn for_each_sample<CB: FnMut(&DataMessage)>(
+    file_path: &str,
+    message_name: &str,
+    multi_id: MultiId,
+    c: &mut CB,
+) -> Result<(), std::io::Error> {
+    let mut callback = |msg: &DataMessage| {
+        if msg.multi_id == multi_id && msg.flattened_format.message_name() == message_name {
+            c(msg);
+        }
+    };
+    let mut parser = LogParser::default();
+    parser.set_data_message_callback(&mut callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)
+}
+
+/// All data messages of a log with their formats, in the order of the log, e.g. for custom
+/// decoders which run after parsing.
+pub fn owned_data_messages(
+    file_path: &str,
+) -> Result<std::vec::IntoIter<OwnedDataMessage>, std::io::Error> {
+    let mut formats: HashMap<MsgId, Arc<FlattenedFormat>> = HashMap::new();
+    let mut messages = Vec::new();
+    let mut callback = |msg: &DataMessage| {
+        let flattened_format = formats
+            .entry(msg.msg_id)
+            .or_insert_with(|| Arc::new(msg.flattened_format.clone()));
+        messages.push(OwnedDataMessage {
+            msg_id: msg.msg_id,
+            multi_id: msg.multi_id.clone(),
+            flattened_format: flattened_format.clone(),
+            data: msg.data.to_vec(),
+        });
+    };
+    let mut parser = LogParser::default();
+    parser.set_data_message_callback(&mut callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+    drop(parser);
+    Ok(messages.into_iter())
+}
 
-    let mut reader = TotalArrayReader::create();
+/// The first sample of every message instance as a row of field name to value, e.g. to preview
+/// the fields of a log with example values. Later samples are not decoded.
+///
+/// This still reads the whole log, since subscriptions to further instances may be added
+/// anywhere in the data section.
+pub fn read_first_samples(file_path: &str) -> Result<InstanceRows, std::io::Error> {
+    let mut seen_msg_ids = HashSet::new();
+    let mut samples = HashMap::new();
     let mut callback = |msg: &DataMessage| {
-        reader.add_message(msg);
+        if !seen_msg_ids.insert(msg.msg_id) {
+            return;
+        }
+        let row = msg
+            .flattened_format
+            .fields
+            .iter()
+            .filter_map(|field| Some((field.flattened_field_name.clone(), field.decode(msg.data)?)))
+            .collect();
+        samples.insert(
+            (
+                msg.flattened_format.message_name.clone(),
+                msg.multi_id.clone(),
+            ),
+            row,
+        );
     };
     let mut parser = LogParser::default();
     parser.set_data_message_callback(&mut callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+    drop(parser);
+    Ok(samples)
+}
+
+/// Calls `c` for every logged string until it returns `SimpleCallbackResult::Stop`, without
+/// storing any data, e.g. to look for the first error of a log.
+pub fn for_each_logged_string<CB: FnMut(&LoggedStringMessage) -> SimpleCallbackResult>(
+    file_path: &str,
+    c: &mut CB,
+) -> Result<(), std::io::Error> {
+    let stopped = Cell::new(false);
+    let mut callback = |msg: &LoggedStringMessage| {
+        // The rest of the current chunk is still parsed after stopping.
+        if stopped.get() {
+            return;
+        }
+        if let SimpleCallbackResult::Stop = c(msg) {
+            stopped.set(true);
+        }
+    };
+    let mut parser = LogParser::default();
+    parser.set_logged_string_message_callback(&mut callback);
+    consume_reader_until(std::fs::File::open(file_path)?, &mut parser, || {
+        stopped.get()
+    })
+}
 
+// Feeds everything from the reader to the parser.
+fn consume_reader<R: Read>(f: R, parser: &mut LogParser) -> Result<(), std::io::Error> {
+    consume_reader_until(f, parser, || false)
+}
+
+// Like consume_reader, but stops reading further chunks once stop returns true.
+fn consume_reader_until<R: Read, S: Fn() -> bool>(
+    mut f: R,
+    parser: &mut LogParser,
+    stop: S,
+) -> Result<(), std::io::Error> {
     const READ_START: usize = 64 * 1024;
     let mut buf = [0u8; 1024 * 1024];
-    loop {
+    while !stop() {
         let num_bytes_read = f.read(&mut buf[READ_START..])?;
         if num_bytes_read == 0 {
             break;
         }
         parser
             .consume_bytes(&buf[READ_START..(READ_START + num_bytes_read)])
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("err: {:?}", e)))?;
+            .map_err(|e| std::io::Error::other(format!("err: {:?}", e)))?;
     }
-    let mut data_format = parser.get_final_data_format();
+    Ok(())
+}
 
-    let mut messages = HashMap::<String, HashMap<MultiId, HashMap<String, SomeVec>>>::new();
-    for msg_id in 0..reader.messages.len() {
-        let fields = &mut reader.messages[msg_id];
-        let msg_id = msg_id as u16;
-        if let Some(description) = data_format.get_message_description(msg_id) {
-            if fields.is_empty() {
-                continue;
-            }
-            if description.0.fields.len() != fields.len() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Mismatch between schema and reality",
-                ));
-            }
-            let field_map = messages
-                .entry(description.0.message_name.to_string())
-                .or_default()
-                .entry(description.1.clone())
-                .or_default();
-            for (field_index, field) in fields.drain(0..).enumerate() {
-                field_map.insert(
-                    description.0.fields[field_index]
-                        .flattened_field_name
-                        .to_string(),
-                    field,
-                );
-            }
+/// Reads a log asynchronously from any tokio `AsyncRead`, e.g. a network stream.
+///
+/// Only the reading is asynchronous, each chunk is parsed synchronously once it arrived.
+/// The returned future is not `Send`, since the parser holds non-`Send` callbacks.
+#[cfg(feature = "tokio")]
+pub async fn read_async<R: tokio::io::AsyncRead + Unpin>(
+    mut async_reader: R,
+) -> Result<ParsedData, std::io::Error> {
+    use tokio::io::AsyncReadExt;
+
+    let collector = RefCell::new(LogCollector::default());
+    collecting_parser!(collector, parser);
+
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let num_bytes_read = async_reader.read(&mut buf).await?;
+        if num_bytes_read == 0 {
+            break;
         }
+        parser
+            .consume_bytes(&buf[..num_bytes_read])
+            .map_err(|e| std::io::Error::other(format!("err: {:?}", e)))?;
     }
-
-    Ok(ParsedData { messages })
+    collector.take().into_parsed_data(parser)
 }
 
 #[derive(Clone, Debug)]
@@ -98,6 +1326,45 @@ macro_rules! vec_push_matcher {
     )
 }
 
+macro_rules! vec_get_matcher {
+    ($self_i:ident, $index:ident, $( $type:tt ),*) => (
+        match $self_i {
+            $(SomeVec::$type(vec) => vec.get($index).map(|v| FlattenedFieldValue::$type(*v)),)+
+        }
+    )
+}
+
+macro_rules! vec_len_matcher {
+    ($self_i:ident, $( $type:tt ),*) => (
+        match $self_i {
+            $(SomeVec::$type(vec) => vec.len(),)+
+        }
+    )
+}
+
+macro_rules! vec_varies_matcher {
+    ($self_i:ident, $( $type:tt ),*) => (
+        match $self_i {
+            $(SomeVec::$type(vec) => vec.iter().any(|v| *v != vec[0]),)+
+            // Compare the bits, so NaN equals NaN
+            SomeVec::Float(vec) => vec.iter().any(|v| v.to_bits() != vec[0].to_bits()),
+            SomeVec::Double(vec) => vec.iter().any(|v| v.to_bits() != vec[0].to_bits()),
+        }
+    )
+}
+
+macro_rules! vec_f64_matcher {
+    ($self_i:ident, $( $type:tt ),*) => (
+        match $self_i {
+            $(SomeVec::$type(vec) => {
+                Box::new(vec.iter().map(|v| *v as f64)) as Box<dyn Iterator<Item = f64>>
+            },)+
+            SomeVec::Bool(vec) => Box::new(vec.iter().map(|v| *v as u8 as f64)),
+            SomeVec::Char(_) => return None,
+        }
+    )
+}
+
 impl SomeVec {
     fn push(&mut self, value: &FlattenedFieldValue) {
         vec_push_matcher!(
@@ -105,6 +1372,44 @@ impl SomeVec {
             Bool, Char
         );
     }
+
+    /// The value at index, if there is one
+    pub fn get(&self, index: usize) -> Option<FlattenedFieldValue> {
+        vec_get_matcher!(
+            self, index, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float, Double,
+            Bool, Char
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        vec_len_matcher!(
+            self, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float, Double, Bool,
+            Char
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether there is more than one distinct value. Floats are compared bitwise.
+    pub fn varies(&self) -> bool {
+        vec_varies_matcher!(
+            self, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Bool, Char
+        )
+    }
+
+    /// The values widened to f64, with NaN replaced by `nan_replacement` if one is given.
+    /// Bools are 0 or 1, chars are not numeric and give None.
+    pub fn iter_f64(&self, nan_replacement: Option<f64>) -> Option<impl Iterator<Item = f64> + '_> {
+        let values = vec_f64_matcher!(
+            self, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float, Double
+        );
+        Some(values.map(move |v| match nan_replacement {
+            Some(replacement) if v.is_nan() => replacement,
+            _ => v,
+        }))
+    }
 }
 
 macro_rules! vec_creation_matcher {
@@ -135,57 +1440,265 @@ fn make_initial_vec(flattened_field_type: &FlattenedFieldType) -> SomeVec {
     )
 }
 
-fn deserialize_field(field: &FlattenedField, mut serialized: &[u8]) -> FlattenedFieldValue {
-    serialized = &serialized[field.offset as usize..];
-    match field.field_type {
-        FlattenedFieldType::Int8 => FlattenedFieldValue::Int8(i8::parse(serialized)),
-        FlattenedFieldType::UInt8 => FlattenedFieldValue::UInt8(u8::parse(serialized)),
-        FlattenedFieldType::Int16 => FlattenedFieldValue::Int16(i16::parse(serialized)),
-        FlattenedFieldType::UInt16 => FlattenedFieldValue::UInt16(u16::parse(serialized)),
-        FlattenedFieldType::Int32 => FlattenedFieldValue::Int32(i32::parse(serialized)),
-        FlattenedFieldType::UInt32 => FlattenedFieldValue::UInt32(u32::parse(serialized)),
-        FlattenedFieldType::Int64 => FlattenedFieldValue::Int64(i64::parse(serialized)),
-        FlattenedFieldType::UInt64 => FlattenedFieldValue::UInt64(u64::parse(serialized)),
-        FlattenedFieldType::Float => FlattenedFieldValue::Float(f32::parse(serialized)),
-        FlattenedFieldType::Double => FlattenedFieldValue::Double(f64::parse(serialized)),
-        FlattenedFieldType::Bool => FlattenedFieldValue::Bool(bool::parse(serialized)),
-        FlattenedFieldType::Char => FlattenedFieldValue::Char(char::parse(serialized)),
-    }
-}
+// (message name, multi_id) -> flattened field name -> value of a single sample
+type InstanceRows = HashMap<(String, MultiId), HashMap<String, FlattenedFieldValue>>;
 
+// message name -> multi_id -> flattened field name -> column, as in ParsedData::messages
+type MessageColumns = HashMap<String, HashMap<MultiId, HashMap<String, SomeVec>>>;
+
+#[derive(Default)]
 struct TotalArrayReader {
-    messages: Vec<Vec<SomeVec>>,
+    // The columns of every msg_id in the order of their first sample. Logs may use few, but
+    // high msg_ids, so they are looked up through column_index instead of indexing directly.
+    messages: Vec<(MsgId, Vec<SomeVec>)>,
+    column_index: HashMap<MsgId, usize>,
+    sample_counts: Vec<usize>,
+    // The index into messages of the sample being pushed
+    current: usize,
+    max_samples: Option<usize>,
 }
 
-impl TotalArrayReader {
-    fn create() -> TotalArrayReader {
-        let messages = Vec::new();
-        Self { messages }
+impl ColumnSink for TotalArrayReader {
+    fn begin_sample(&mut self, msg: &DataMessage) -> bool {
+        self.current = match self.column_index.get(&msg.msg_id) {
+            Some(index) => *index,
+            None => {
+                let columns = msg
+                    .flattened_format
+                    .fields
+                    .iter()
+                    .map(|field| make_initial_vec(&field.field_type))
+                    .collect();
+                self.messages.push((msg.msg_id, columns));
+                self.sample_counts.push(0);
+                self.column_index
+                    .insert(msg.msg_id, self.messages.len() - 1);
+                self.messages.len() - 1
+            }
+        };
+        let sample_count = &mut self.sample_counts[self.current];
+        *sample_count += 1;
+        match self.max_samples {
+            Some(max) => *sample_count <= max,
+            None => true,
+        }
+    }
+
+    fn push_value(&mut self, field: FieldId, value: FlattenedFieldValue) {
+        self.messages[self.current].1[field.field_index].push(&value);
+    }
+}
+
+impl TotalArrayReader {
+    // Also returns the message instances which had samples dropped because of max_samples.
+    fn into_messages(
+        self,
+        data_format: &mut DataFormat,
+    ) -> Result<(MessageColumns, HashSet<(String, MultiId)>), std::io::Error> {
+        let mut messages = MessageColumns::new();
+        let mut capped = HashSet::new();
+        for ((msg_id, fields), sample_count) in self.messages.into_iter().zip(self.sample_counts) {
+            if let Some(description) = data_format.get_message_description(msg_id) {
+                if self.max_samples.is_some_and(|max| sample_count > max) {
+                    capped.insert((
+                        description.0.message_name.to_string(),
+                        description.1.clone(),
+                    ));
+                }
+                if fields.is_empty() {
+                    continue;
+                }
+                if description.0.fields.len() != fields.len() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Mismatch between schema and reality",
+                    ));
+                }
+                let field_map = messages
+                    .entry(description.0.message_name.to_string())
+                    .or_default()
+                    .entry(description.1.clone())
+                    .or_default();
+                for (field_index, field) in fields.into_iter().enumerate() {
+                    field_map.insert(
+                        description.0.fields[field_index]
+                            .flattened_field_name
+                            .to_string(),
+                        field,
+                    );
+                }
+            }
+        }
+
+        Ok((messages, capped))
     }
+}
+
+// Gathers everything but the data format while parsing, see collecting_parser.
+#[derive(Default)]
+struct LogCollector {
+    reader: TotalArrayReader,
+    parameters: HashMap<String, ParamValue>,
+    changed_parameters: Vec<(u64, String, ParamValue)>,
+    default_parameters: HashMap<String, ParamValue>,
+    airframe_default_parameters: HashMap<String, ParamValue>,
+    info: HashMap<String, InfoValue>,
+    multiple_info: HashMap<String, Vec<Vec<u8>>>,
+    logged_strings: Vec<LoggedString>,
+    dropouts: Vec<Dropout>,
+    stats: ParseStats,
+    // The latest timestamp of any data message so far
+    last_timestamp: u64,
+}
 
-    fn add_message(&mut self, msg: &DataMessage) {
-        if msg.msg_id as usize >= self.messages.len() {
-            self.messages.resize(msg.msg_id as usize + 1, Vec::new());
+impl LogCollector {
+    fn add_data(&mut self, msg: &DataMessage) {
+        if let Some(timestamp) = msg.timestamp() {
+            self.last_timestamp = self.last_timestamp.max(timestamp);
         }
-        let field_values = &mut self.messages[msg.msg_id as usize];
-        if field_values.is_empty() {
-            field_values.reserve(msg.flattened_format.fields.len() + 1);
-            for field in &msg.flattened_format.fields {
-                field_values.push(make_initial_vec(&field.field_type))
+        sink::push_sample(&mut self.reader, msg);
+    }
+
+    fn add_parameter(&mut self, msg: &ParameterMessage) {
+        let (name, value, log_stage) = match split_parameter(msg) {
+            Some(parameter) => parameter,
+            None => return,
+        };
+        match log_stage {
+            LogStage::Definitions => {
+                self.parameters.insert(name.to_string(), value);
+            }
+            LogStage::Data => {
+                self.changed_parameters
+                    .push((self.last_timestamp, name.to_string(), value))
             }
         }
+    }
+
+    fn add_default_parameter(&mut self, msg: &DefaultParameterMessage) {
+        let (name, value, _) = match split_parameter(&msg.parameter) {
+            Some(parameter) => parameter,
+            None => return,
+        };
+        if msg.is_default_type(DefaultType::System) {
+            self.default_parameters.insert(name.to_string(), value);
+        }
+        if msg.is_default_type(DefaultType::Airframe) {
+            self.airframe_default_parameters
+                .insert(name.to_string(), value);
+        }
+    }
+
+    fn add_info(&mut self, msg: &InfoMessage) {
+        self.info
+            .insert(msg.key.to_string(), InfoValue::from_message(msg));
+    }
+
+    fn add_multiple_info(&mut self, msg: &MultipleInfoMessage) {
+        let values = self.multiple_info.entry(msg.key.to_string()).or_default();
+        match values.last_mut() {
+            Some(value) if msg.is_continued => value.extend_from_slice(msg.value),
+            _ => values.push(msg.value.to_vec()),
+        }
+    }
+
+    fn add_logged_string(&mut self, msg: &LoggedStringMessage) {
+        self.logged_strings.push(LoggedString {
+            log_level: msg.log_level,
+            timestamp: msg.timestamp,
+            message: msg.logged_message.to_string(),
+        });
+    }
+
+    fn add_dropout(&mut self, msg: &DropoutMessage) {
+        self.dropouts.push(Dropout {
+            duration_ms: msg.duration_ms,
+            timestamp: self.last_timestamp,
+        });
+    }
+
+    fn into_parsed_data(self, mut parser: LogParser) -> Result<ParsedData, std::io::Error> {
+        let definition_bytes = parser.take_definition_bytes();
+        let version = parser.version();
+        let start_timestamp = parser.start_timestamp();
+        let mut data_format = parser.get_final_data_format();
+        let (messages, capped_messages) = self.reader.into_messages(&mut data_format)?;
+        Ok(ParsedData {
+            messages,
+            capped_messages,
+            formats: data_format.take_flattened_formats(),
+            version,
+            start_timestamp,
+            parameters: self.parameters,
+            changed_parameters: self.changed_parameters,
+            default_parameters: self.default_parameters,
+            airframe_default_parameters: self.airframe_default_parameters,
+            info: self.info,
+            multiple_info: self.multiple_info,
+            logged_strings: self.logged_strings,
+            dropouts: self.dropouts,
+            definition_bytes,
+        })
+    }
+}
+
+// Roll, pitch and yaw of the rotation, with the Tait-Bryan (z-y'-x'') convention PX4 uses
+fn quaternion_to_euler(w: f32, x: f32, y: f32, z: f32) -> (f32, f32, f32) {
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+    let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+    (roll, pitch, yaw)
+}
+
+fn is_sorted(timestamps: &[u64]) -> bool {
+    timestamps.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+// The index of the timestamp nearest to t_us, the first one on ties. Sorted timestamps are
+// searched, others are scanned completely.
+fn nearest_index(timestamps: &[u64], sorted: bool, t_us: u64) -> Option<usize> {
+    if !sorted {
+        return timestamps
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timestamp)| timestamp.abs_diff(t_us))
+            .map(|(index, _)| index);
+    }
+    let after = timestamps.partition_point(|timestamp| *timestamp < t_us);
+    if after == 0 {
+        return if timestamps.is_empty() { None } else { Some(0) };
+    }
+    if after == timestamps.len() || t_us - timestamps[after - 1] <= timestamps[after] - t_us {
+        return Some(after - 1);
+    }
+    Some(after)
+}
+
+// The values of all fields of the sample at index
+fn row(fields: &HashMap<String, SomeVec>, index: usize) -> HashMap<String, FlattenedFieldValue> {
+    fields
+        .iter()
+        .filter_map(|(name, values)| Some((name.clone(), values.get(index)?)))
+        .collect()
+}
 
-        for i in 0..msg.flattened_format.fields.len() {
-            let el = &msg.flattened_format.fields[i];
-            let value = deserialize_field(&el, msg.data);
-            field_values[i].push(&value);
+// Returns None for parameters of other types, which only lenient parsers pass on.
+fn split_parameter<'m>(msg: &'m ParameterMessage) -> Option<(&'m str, ParamValue, &'m LogStage)> {
+    match msg {
+        ParameterMessage::Int32(name, value, log_stage) => {
+            Some((*name, ParamValue::Int32(*value), log_stage))
         }
+        ParameterMessage::Float(name, value, log_stage) => {
+            Some((*name, ParamValue::Float(*value), log_stage))
+        }
+        ParameterMessage::Raw(..) => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stream_parser::LittleEndianParser;
 
     #[test]
     fn reads_log_file() {
@@ -218,4 +1731,1263 @@ mod tests {
 
         assert!(msg.contains_key("esc[5].esc_rpm"));
     }
+
+    #[test]
+    fn calls_back_for_each_sample() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut count = 0;
+        let mut last_timestamp = 0;
+        for_each_sample(
+            &filename,
+            "vehicle_gps_position",
+            MultiId::new(0),
+            &mut |msg: &DataMessage| {
+                let parser = msg
+                    .flattened_format
+                    .get_field_parser::<u64>("timestamp")
+                    .unwrap();
+                last_timestamp = parser.parse(msg.data);
+                count += 1;
+            },
+        )
+        .unwrap();
+
+        let parsed_data = read_file(&filename).unwrap();
+        let timestamps =
+            &parsed_data.messages["vehicle_gps_position"][&MultiId::new(0)]["timestamp"];
+        match timestamps {
+            SomeVec::UInt64(timestamps) => {
+                assert_eq!(timestamps.len(), count);
+                assert_eq!(*timestamps.last().unwrap(), last_timestamp);
+            }
+            _ => panic!("unexpected timestamp type"),
+        }
+    }
+
+    #[test]
+    fn borrows_timestamps() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let timestamps = parsed_data
+            .timestamps("vehicle_gps_position", MultiId::new(0))
+            .unwrap();
+        match &parsed_data.messages["vehicle_gps_position"][&MultiId::new(0)]["lat"] {
+            SomeVec::Int32(lat) => assert_eq!(lat.len(), timestamps.len()),
+            _ => panic!("unexpected lat type"),
+        }
+        assert_eq!(375408345, timestamps[0]);
+        assert!(parsed_data
+            .timestamps("vehicle_gps_position", MultiId::new(1))
+            .is_none());
+        assert!(parsed_data
+            .timestamps("no_such_message", MultiId::new(0))
+            .is_none());
+    }
+
+    #[test]
+    fn caps_samples_per_topic() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file_capped(&filename, 5).unwrap();
+        let timestamps = parsed_data
+            .timestamps("vehicle_gps_position", MultiId::new(0))
+            .unwrap();
+        assert_eq!(5, timestamps.len());
+        assert_eq!(375408345, timestamps[0]);
+        assert!(parsed_data
+            .capped_messages
+            .contains(&("vehicle_gps_position".to_string(), MultiId::new(0))));
+        // vehicle_command has a single sample only
+        assert_eq!(
+            1,
+            parsed_data
+                .timestamps("vehicle_command", MultiId::new(0))
+                .unwrap()
+                .len()
+        );
+        assert!(!parsed_data
+            .capped_messages
+            .contains(&("vehicle_command".to_string(), MultiId::new(0))));
+        assert!(read_file(&filename).unwrap().capped_messages.is_empty());
+    }
+
+    #[test]
+    fn caps_all_samples_at_zero() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file_capped(&filename, 0).unwrap();
+        let instance_count: usize = read_file(&filename)
+            .unwrap()
+            .messages
+            .values()
+            .map(HashMap::len)
+            .sum();
+        assert_eq!(instance_count, parsed_data.capped_messages.len());
+        assert!(parsed_data
+            .capped_messages
+            .contains(&("vehicle_command".to_string(), MultiId::new(0))));
+        assert_eq!(
+            Some(0),
+            parsed_data
+                .timestamps("vehicle_gps_position", MultiId::new(0))
+                .map(<[u64]>::len)
+        );
+    }
+
+    #[test]
+    fn summarizes_in_one_line() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let summary = read_file(&filename).unwrap().summary_line();
+        assert!(summary.starts_with("v0 log, "), "{}", summary);
+        assert!(summary.contains(" 13 topics, "), "{}", summary);
+        assert!(summary.ends_with(" samples, 4 dropouts"), "{}", summary);
+    }
+
+    #[test]
+    fn summarizes_in_one_line_with_timestamp_reset() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat");
+        for timestamp in &[5000u64, 6000, 7000, 100] {
+            log = log.data(0, &timestamp.to_le_bytes());
+        }
+        let summary = read_from_reader(log.bytes().as_slice())
+            .unwrap()
+            .summary_line();
+        assert!(
+            summary.contains(", 0.0 s, 1 topics, 4 samples, "),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn sorts_logged_strings_by_severity() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let mut parsed_data = read_file(&filename).unwrap();
+        parsed_data.logged_strings.push(LoggedString {
+            log_level: b'6',
+            timestamp: 0,
+            message: "info".to_string(),
+        });
+        parsed_data.logged_strings.push(LoggedString {
+            log_level: b'0',
+            timestamp: u64::MAX,
+            message: "emergency".to_string(),
+        });
+        let sorted = parsed_data.logged_strings_by_severity();
+        assert_eq!(6, sorted.len());
+        assert_eq!("emergency", sorted[0].message);
+        assert_eq!("info", sorted[5].message);
+        // The errors of the log keep their chronological order
+        assert!(sorted[1..5].iter().all(|s| s.log_level == b'3'));
+        assert!(sorted[1..5]
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+
+    #[test]
+    fn rebases_logged_strings_to_first_data() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let logged_strings = parsed_data.logged_strings_relative_to_data().unwrap();
+        assert_eq!(2, logged_strings.len());
+        assert_eq!(330430986 - 183984857, logged_strings[0].0);
+        assert!(logged_strings[0].1.message.contains("Accel #1 fail"));
+    }
+
+    #[test]
+    fn reads_bool_array() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let sample = |timestamp: u64, flags: [u8; 4]| {
+            let mut payload = timestamp.to_le_bytes().to_vec();
+            payload.extend_from_slice(&flags);
+            payload.push(0xff);
+            payload
+        };
+        let log = TestLog::new()
+            .format("status:uint64_t timestamp;bool[4] flags;uint8_t after")
+            .add_logged_message(0, 0, "status")
+            .data(0, &sample(1, [1, 0, 0, 1]))
+            .data(0, &sample(2, [0, 1, 1, 0]))
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        let fields = &parsed_data.messages["status"][&MultiId::new(0)];
+        let column = |index: usize| match &fields[&format!("flags[{}]", index)] {
+            SomeVec::Bool(values) => values.clone(),
+            _ => panic!("unexpected type of flags[{}]", index),
+        };
+        assert_eq!(vec![true, false], column(0));
+        assert_eq!(vec![false, true], column(1));
+        assert_eq!(vec![false, true], column(2));
+        assert_eq!(vec![true, false], column(3));
+        match &fields["after"] {
+            SomeVec::UInt8(after) => assert_eq!(&vec![0xff, 0xff], after),
+            _ => panic!("unexpected type of after"),
+        }
+    }
+
+    #[test]
+    fn reads_sparse_msg_ids() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .format("first:uint64_t timestamp;int32_t value")
+            .format("second:uint64_t timestamp;float value")
+            .add_logged_message(0, 5000, "second")
+            .add_logged_message(0, 0, "first")
+            .data(
+                5000,
+                &[&1u64.to_le_bytes()[..], &0.5f32.to_le_bytes()].concat(),
+            )
+            .data(0, &[&2u64.to_le_bytes()[..], &7i32.to_le_bytes()].concat())
+            .data(
+                5000,
+                &[&3u64.to_le_bytes()[..], &1.5f32.to_le_bytes()].concat(),
+            )
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        match &parsed_data.messages["first"][&MultiId::new(0)]["value"] {
+            SomeVec::Int32(values) => assert_eq!(&vec![7], values),
+            _ => panic!("unexpected type of first.value"),
+        }
+        match &parsed_data.messages["second"][&MultiId::new(0)]["value"] {
+            SomeVec::Float(values) => assert_eq!(&vec![0.5, 1.5], values),
+            _ => panic!("unexpected type of second.value"),
+        }
+    }
+
+    #[test]
+    fn reads_timestamp_only_message() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .format("keep_alive:uint64_t timestamp")
+            .add_logged_message(0, 0, "keep_alive")
+            .data(0, &10u64.to_le_bytes())
+            .data(0, &20u64.to_le_bytes())
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        let format = &parsed_data.formats["keep_alive"];
+        assert_eq!(10, format.size());
+        let mut data = vec![0, 0];
+        data.extend_from_slice(&20u64.to_le_bytes());
+        let timestamp_field = format.timestamp_field.as_ref().unwrap();
+        assert_eq!(Some(20), timestamp_field.parse_timestamp(&data));
+        assert_eq!(None, timestamp_field.parse_timestamp(&data[..9]));
+        assert_eq!(
+            Some(&[10u64, 20][..]),
+            parsed_data.timestamps("keep_alive", MultiId::new(0))
+        );
+    }
+
+    #[test]
+    fn returns_latest_samples() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let rows = parsed_data.tail("vehicle_gps_position", MultiId::new(0), 3);
+        assert_eq!(3, rows.len());
+        let fields = &parsed_data.messages["vehicle_gps_position"][&MultiId::new(0)];
+        let (timestamps, lats) = match (&fields["timestamp"], &fields["lat"]) {
+            (SomeVec::UInt64(timestamps), SomeVec::Int32(lats)) => (timestamps, lats),
+            _ => panic!("unexpected field types"),
+        };
+        let first_index = timestamps.len() - 3;
+        for (row, index) in rows.iter().zip(first_index..) {
+            assert_eq!(fields.len(), row.len());
+            match (&row["timestamp"], &row["lat"]) {
+                (FlattenedFieldValue::UInt64(timestamp), FlattenedFieldValue::Int32(lat)) => {
+                    assert_eq!(timestamps[index], *timestamp);
+                    assert_eq!(lats[index], *lat);
+                }
+                _ => panic!("unexpected value types"),
+            }
+        }
+
+        assert_eq!(
+            1,
+            parsed_data
+                .tail("vehicle_command", MultiId::new(0), 3)
+                .len()
+        );
+        assert!(parsed_data
+            .tail("no_such_message", MultiId::new(0), 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn gathers_parse_stats() {
+        use crate::parser::message::ULogMessageSource;
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let (parsed_data, stats) = read_file_with_stats(&filename).unwrap();
+        let mut file = std::fs::File::open(&filename).unwrap();
+        let file_len = file.metadata().unwrap().len() as usize;
+        assert_eq!(file.messages().count(), stats.messages_parsed);
+        assert_eq!(file_len, stats.bytes_consumed);
+        assert_eq!(parsed_data.formats.len(), stats.formats_defined);
+        assert_eq!(43, stats.subscriptions);
+    }
+
+    #[test]
+    fn reassembles_multiple_info() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .multiple_info(false, "uint8_t[3] hardware_version_main", &[1, 2, 3])
+            .multiple_info(false, "uint8_t[1] hardware_version_io", &[9])
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat")
+            .data(0, &100u64.to_le_bytes())
+            .multiple_info(true, "uint8_t[2] hardware_version_main", &[4, 5])
+            .multiple_info(false, "uint8_t[1] hardware_version_main", &[6])
+            .multiple_info(false, "uint8_t[1] perf_counter", &[7])
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        let hardware_versions = parsed_data.multiple_info_with_prefix("hardware_version");
+        assert_eq!(2, hardware_versions.len());
+        assert_eq!(
+            ("hardware_version_io", &[vec![9]][..]),
+            hardware_versions[0]
+        );
+        assert_eq!(
+            ("hardware_version_main", &[vec![1, 2, 3, 4, 5], vec![6]][..]),
+            hardware_versions[1]
+        );
+    }
+
+    #[test]
+    fn decodes_owned_messages_after_parsing() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let gps_messages: Vec<OwnedDataMessage> = owned_data_messages(&filename)
+            .unwrap()
+            .filter(|msg| msg.flattened_format.message_name() == "vehicle_gps_position")
+            .collect();
+        assert_eq!(260, gps_messages.len());
+        assert!(Arc::ptr_eq(
+            &gps_messages[0].flattened_format,
+            &gps_messages[259].flattened_format
+        ));
+        let lat_parser = gps_messages[0]
+            .flattened_format
+            .get_field_parser::<i32>("lat")
+            .unwrap();
+        let timestamps: Vec<u64> = gps_messages
+            .iter()
+            .map(|msg| msg.as_data_message().timestamp().unwrap())
+            .collect();
+        assert_eq!(375408345, timestamps[0]);
+        assert_eq!(407423012, lat_parser.parse(&gps_messages[0].data));
+
+        let parsed_data = read_file(&filename).unwrap();
+        assert_eq!(
+            parsed_data.timestamps("vehicle_gps_position", MultiId::new(0)),
+            Some(&timestamps[..])
+        );
+    }
+
+    #[test]
+    fn decodes_only_timestamp() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut timestamps = Vec::new();
+        for_each_sample(
+            &filename,
+            "vehicle_gps_position",
+            MultiId::new(0),
+            &mut |msg: &DataMessage| {
+                let parser = msg
+                    .flattened_format
+                    .get_field_parser::<u64>("timestamp")
+                    .unwrap();
+                assert_eq!(Some(parser.parse(msg.data)), msg.timestamp());
+                timestamps.push(msg.timestamp().unwrap());
+            },
+        )
+        .unwrap();
+        let parsed_data = read_file(&filename).unwrap();
+        assert_eq!(
+            parsed_data.timestamps("vehicle_gps_position", MultiId::new(0)),
+            Some(&timestamps[..])
+        );
+    }
+
+    #[test]
+    fn finds_armed_intervals() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        assert_eq!(
+            vec![(5081572164, 5120372183)],
+            parsed_data.armed_intervals()
+        );
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        assert!(read_file(&filename).unwrap().armed_intervals().is_empty());
+    }
+
+    #[test]
+    fn does_not_count_standby_error_as_armed() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("vehicle_status:uint64_t timestamp;uint8_t arming_state")
+            .add_logged_message(0, 0, "vehicle_status");
+        // STANDBY, STANDBY_ERROR, ARMED, STANDBY, STANDBY_ERROR
+        for (timestamp, arming_state) in &[(100u64, 1u8), (200, 3), (300, 2), (400, 1), (500, 3)] {
+            log = log.data(
+                0,
+                &[&timestamp.to_le_bytes()[..], &[*arming_state]].concat(),
+            );
+        }
+        let parsed_data = read_from_reader(log.bytes().as_slice()).unwrap();
+        assert_eq!(vec![(300, 400)], parsed_data.armed_intervals());
+    }
+
+    #[test]
+    fn finds_value_intervals() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("sensor:uint64_t timestamp;float temperature")
+            .add_logged_message(0, 0, "sensor");
+        let samples = [
+            (100u64, 20.0f32),
+            (200, 41.0),
+            (300, 45.0),
+            (400, 39.0),
+            (500, 40.5),
+            (600, f32::NAN),
+            (700, 42.0),
+        ];
+        for (timestamp, temperature) in &samples {
+            log = log.data(
+                0,
+                &[&timestamp.to_le_bytes()[..], &temperature.to_le_bytes()].concat(),
+            );
+        }
+        let parsed_data = read_from_reader(log.bytes().as_slice()).unwrap();
+        assert_eq!(
+            vec![(200, 400), (500, 600), (700, 700)],
+            parsed_data.value_intervals("sensor", MultiId::new(0), "temperature", |t| t > 40.0)
+        );
+        assert!(parsed_data
+            .value_intervals("sensor", MultiId::new(0), "humidity", |_| true)
+            .is_empty());
+        assert!(parsed_data
+            .value_intervals("sensor", MultiId::new(1), "temperature", |_| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn converts_attitude_to_euler_angles() {
+        let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+        let (roll, pitch, yaw) = quaternion_to_euler(half_sqrt2, 0.0, 0.0, half_sqrt2);
+        assert!(roll.abs() < 1e-6 && pitch.abs() < 1e-6);
+        assert!((yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let parsed_data = read_file(&filename).unwrap();
+        let angles = parsed_data.euler_angles(MultiId::new(0)).unwrap();
+        let timestamps = parsed_data
+            .timestamps("vehicle_attitude", MultiId::new(0))
+            .unwrap();
+        assert_eq!(timestamps.len(), angles.len());
+        let pi = std::f32::consts::PI;
+        for (roll, pitch, yaw) in angles {
+            assert!((-pi..=pi).contains(&roll));
+            assert!((-pi / 2.0..=pi / 2.0).contains(&pitch));
+            assert!((-pi..=pi).contains(&yaw));
+        }
+        assert!(parsed_data.euler_angles(MultiId::new(1)).is_none());
+    }
+
+    #[test]
+    fn summarizes_cpu_load() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let summary = read_file(&filename).unwrap().cpu_summary().unwrap();
+        assert!((summary.max_load - 0.833).abs() < 1e-3, "{:?}", summary);
+        assert!((summary.mean_load - 0.546).abs() < 1e-3, "{:?}", summary);
+        assert!(summary.mean_load <= summary.max_load);
+        assert!((summary.max_ram - 0.863).abs() < 1e-3, "{:?}", summary);
+
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        assert_eq!(None, read_file(&filename).unwrap().cpu_summary());
+    }
+
+    #[test]
+    fn extracts_local_trajectory() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let trajectory = parsed_data.local_trajectory(MultiId::new(0)).unwrap();
+        let timestamps = parsed_data
+            .timestamps("vehicle_local_position", MultiId::new(0))
+            .unwrap();
+        assert_eq!(315, trajectory.len());
+        assert_eq!(timestamps.len(), trajectory.len());
+        assert!((trajectory[0].0 - -0.8214531).abs() < 1e-6);
+        assert!(parsed_data.local_trajectory(MultiId::new(1)).is_none());
+    }
+
+    #[test]
+    fn reads_imu_samples() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let samples = parsed_data.imu_samples().unwrap();
+        let timestamps = parsed_data
+            .timestamps("sensor_combined", MultiId::new(0))
+            .unwrap();
+        assert!(!samples.is_empty());
+        assert_eq!(timestamps.len(), samples.len());
+        assert_eq!(timestamps[0], samples[0].timestamp);
+        let fields = &parsed_data.messages["sensor_combined"][&MultiId::new(0)];
+        match fields["accelerometer_m_s2[2]"].get(0) {
+            Some(FlattenedFieldValue::Float(accel_z)) => assert_eq!(accel_z, samples[0].accel[2]),
+            _ => panic!("accelerometer_m_s2[2] is not a float"),
+        }
+        for sample in samples {
+            let gyro_magnitude = sample.gyro.iter().map(|v| v * v).sum::<f32>().sqrt();
+            assert!(gyro_magnitude.is_finite());
+        }
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        assert!(read_file(&filename).unwrap().imu_samples().is_some());
+    }
+
+    #[test]
+    fn reads_mission_events() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let events = read_file(&filename).unwrap().mission_events();
+        assert_eq!(1, events.len());
+        assert_eq!(-1, events[0].seq_reached);
+        assert!(!events[0].finished);
+
+        let mut writer = UlogWriter::new(0);
+        writer
+            .define_message(
+                "mission_result",
+                &[
+                    ("timestamp", FlattenedFieldType::UInt64),
+                    ("seq_reached", FlattenedFieldType::Int32),
+                    ("finished", FlattenedFieldType::Bool),
+                ],
+            )
+            .unwrap()
+            .add_subscription("mission_result", MultiId::new(0))
+            .unwrap();
+        for (timestamp, seq_reached) in &[(1000u64, -1), (2000, 0), (3000, 0), (4000, 2)] {
+            writer
+                .write_data(
+                    "mission_result",
+                    MultiId::new(0),
+                    &[
+                        FlattenedFieldValue::UInt64(*timestamp),
+                        FlattenedFieldValue::Int32(*seq_reached),
+                        FlattenedFieldValue::Bool(*seq_reached == 2),
+                    ],
+                )
+                .unwrap();
+        }
+        let events = read_from_reader(&writer.finish()[..])
+            .unwrap()
+            .mission_events();
+        assert_eq!(4, events.len());
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].seq_reached <= pair[1].seq_reached));
+        assert_eq!(
+            MissionEvent {
+                timestamp: 4000,
+                seq_reached: 2,
+                finished: true
+            },
+            events[3]
+        );
+
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        assert!(read_file(&filename).unwrap().mission_events().is_empty());
+    }
+
+    #[test]
+    fn reads_vehicle_commands() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let commands = read_file(&filename).unwrap().commands();
+        assert_eq!(4, commands.len());
+        // MAV_CMD_LOGGING_START
+        assert_eq!(2510, commands[0].command);
+        assert_eq!(5076509383, commands[0].timestamp);
+        assert!(commands
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        assert!(read_file(&filename).unwrap().commands().is_empty());
+    }
+
+    #[test]
+    fn summarizes_battery() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let summary = parsed_data.battery_summary(MultiId::new(0)).unwrap();
+        // A 6S battery under load
+        assert!(
+            summary.min_voltage > 18.0 && summary.min_voltage < 25.2,
+            "{:?}",
+            summary
+        );
+        assert!(
+            (summary.min_remaining - 0.3616).abs() < 1e-3,
+            "{:?}",
+            summary
+        );
+        assert!((summary.max_current - 73.9).abs() < 1e-2, "{:?}", summary);
+        assert_eq!(0, summary.warning_count);
+        assert_eq!(None, parsed_data.battery_summary(MultiId::new(1)));
+    }
+
+    #[test]
+    fn computes_gps_bounds() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let bounds = read_file(&filename).unwrap().gps_bounds().unwrap();
+        assert!((bounds.min_lat - 40.7423009).abs() < 1e-9);
+        assert!((bounds.max_lat - 40.7423018).abs() < 1e-9);
+        assert!((bounds.min_lon - -74.1793008).abs() < 1e-9);
+        assert!((bounds.max_lon - -74.1792993).abs() < 1e-9);
+
+        use crate::stream_parser::test_util::TestLog;
+        let log = TestLog::new()
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat")
+            .data(0, &100u64.to_le_bytes())
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        assert_eq!(None, parsed_data.gps_bounds());
+    }
+
+    #[test]
+    fn widens_values_to_f64() {
+        let values = SomeVec::Float(vec![1.5, f32::NAN, -2.0]);
+        let widened: Vec<f64> = values.iter_f64(Some(0.0)).unwrap().collect();
+        assert_eq!(vec![1.5, 0.0, -2.0], widened);
+        let widened: Vec<f64> = values.iter_f64(None).unwrap().collect();
+        assert!(widened[1].is_nan());
+
+        let flags = SomeVec::Bool(vec![true, false]);
+        let widened: Vec<f64> = flags.iter_f64(Some(-1.0)).unwrap().collect();
+        assert_eq!(vec![1.0, 0.0], widened);
+        assert!(SomeVec::Char(vec!['a']).iter_f64(None).is_none());
+    }
+
+    #[test]
+    fn returns_timed_rows() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let rows = parsed_data.timed_rows("esc_status", MultiId::new(0));
+        assert_eq!(127, rows.len());
+        let (timestamp, row) = &rows[0];
+        assert_eq!(5081348405, *timestamp);
+        match row.get("esc[0].esc_rpm") {
+            Some(FlattenedFieldValue::Int32(rpm)) => assert_eq!(2465, *rpm),
+            other => panic!("unexpected esc_rpm {:?}", other),
+        }
+        assert!(rows.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(parsed_data
+            .timed_rows("esc_status", MultiId::new(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_differing_schemas_for_one_name() {
+        use crate::stream_parser::test_util::TestLog;
+
+        // Formats are referenced by name only, so there is no way to tell which definition an
+        // instance uses. Such a log is refused instead of decoding an instance with the wrong one.
+        let log = TestLog::new()
+            .format("sensor:uint64_t timestamp;float x")
+            .format("sensor:uint64_t timestamp;int32_t x;int32_t y")
+            .add_logged_message(0, 0, "sensor")
+            .add_logged_message(1, 1, "sensor")
+            .data(
+                0,
+                &[&1u64.to_le_bytes()[..], &1.5f32.to_le_bytes()].concat(),
+            )
+            .bytes();
+        let error = read_from_reader(log.as_slice()).err().unwrap();
+        assert!(error.to_string().contains("duplicate message definition"));
+    }
+
+    #[test]
+    fn finds_timestamp_resets() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat");
+        for timestamp in &[100u64, 200, 300, 50, 150, 150, 0] {
+            log = log.data(0, &timestamp.to_le_bytes());
+        }
+        let parsed_data = read_from_reader(log.bytes().as_slice()).unwrap();
+        assert_eq!(
+            vec![3, 5],
+            parsed_data.timestamp_resets("heartbeat", MultiId::new(0))
+        );
+        assert!(parsed_data
+            .timestamp_resets("heartbeat", MultiId::new(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn computes_completeness() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let completeness = parsed_data
+            .completeness("vehicle_gps_position", MultiId::new(0))
+            .unwrap();
+        assert!((0.98..=1.0).contains(&completeness), "{}", completeness);
+        // A single sample has no period
+        assert!(parsed_data
+            .completeness("vehicle_command", MultiId::new(0))
+            .is_none());
+        assert!(parsed_data
+            .completeness("no_such_message", MultiId::new(0))
+            .is_none());
+    }
+
+    #[test]
+    fn computes_completeness_with_timestamp_reset() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat");
+        for timestamp in &[5000u64, 6000, 7000, 100] {
+            log = log.data(0, &timestamp.to_le_bytes());
+        }
+        let parsed_data = read_from_reader(log.bytes().as_slice()).unwrap();
+        // The span from 100 to 7000 at a period of 1000 would hold 8 samples
+        assert_eq!(
+            Some(0.5),
+            parsed_data.completeness("heartbeat", MultiId::new(0))
+        );
+    }
+
+    #[test]
+    fn stops_at_first_error_string() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut seen = Vec::new();
+        for_each_logged_string(&filename, &mut |msg: &LoggedStringMessage| {
+            seen.push(msg.logged_message.to_string());
+            if msg.human_readable_log_level() == "ERROR" {
+                SimpleCallbackResult::Stop
+            } else {
+                SimpleCallbackResult::KeepReading
+            }
+        })
+        .unwrap();
+        assert_eq!(vec!["[sensors] Accel #1 fail:  TIMEOUT!".to_string()], seen);
+    }
+
+    #[test]
+    fn reads_everything_in_log() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let parsed_data = read_file(&filename).unwrap();
+
+        assert!(parsed_data.messages.contains_key("vehicle_attitude"));
+        assert!(parsed_data.formats.contains_key("vehicle_attitude"));
+        assert_eq!(0, parsed_data.version);
+        assert_eq!(112500176, parsed_data.start_timestamp);
+        assert_eq!(
+            Some(&ParamValue::Int32(1)),
+            parsed_data.parameters.get("COM_AUTOS_PAR")
+        );
+        assert_eq!("COM_AUTOS_PAR", parsed_data.changed_parameters[0].1);
+        assert_eq!(
+            Some("AUAV_X21"),
+            parsed_data.info.get("ver_hw").and_then(|v| v.as_str())
+        );
+        assert_eq!(4, parsed_data.logged_strings.len());
+        assert!(parsed_data.logged_strings[0]
+            .message
+            .contains("no barometer found"));
+        assert_eq!(4, parsed_data.dropouts.len());
+        assert_eq!(26, parsed_data.dropouts[1].duration_ms);
+    }
+
+    #[test]
+    fn tracks_parameter_changes() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let parsed_data = read_file(&filename).unwrap();
+        let history = parsed_data.parameter_history("COM_AUTOS_PAR");
+        assert_eq!(5, history.len());
+        assert_eq!((112500176, ParamValue::Int32(1)), history[0]);
+        let changes = parsed_data.parameter_changes("COM_AUTOS_PAR");
+        assert_eq!(
+            (158196367, ParamValue::Int32(1), ParamValue::Int32(0)),
+            changes[0]
+        );
+        assert_eq!(
+            (162054776, ParamValue::Int32(0), ParamValue::Int32(1)),
+            changes[1]
+        );
+        assert!(parsed_data.parameter_changes("NO_SUCH_PARAM").is_empty());
+    }
+
+    #[test]
+    fn lists_non_default_parameters() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .default_parameter(3, "int32_t SYS_AUTOSTART", 0i32.to_le_bytes())
+            .default_parameter(1, "float MC_ROLL_P", 6.5f32.to_le_bytes())
+            .default_parameter(2, "float MC_PITCH_P", 6.5f32.to_le_bytes())
+            .parameter("int32_t SYS_AUTOSTART", 4001i32.to_le_bytes())
+            .parameter("float MC_ROLL_P", 6.5f32.to_le_bytes())
+            .parameter("float MC_PITCH_P", 7.0f32.to_le_bytes())
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat")
+            .data(0, &100u64.to_le_bytes())
+            .parameter("float MC_ROLL_P", 7.5f32.to_le_bytes())
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        assert_eq!(2, parsed_data.default_parameters.len());
+        assert_eq!(
+            vec![
+                (
+                    "MC_ROLL_P".to_string(),
+                    ParamValue::Float(7.5),
+                    ParamValue::Float(6.5)
+                ),
+                (
+                    "SYS_AUTOSTART".to_string(),
+                    ParamValue::Int32(4001),
+                    ParamValue::Int32(0)
+                ),
+            ],
+            parsed_data.non_default_parameters()
+        );
+    }
+
+    #[test]
+    fn filters_default_parameters_by_type() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .default_parameter(3, "int32_t SYS_AUTOSTART", 0i32.to_le_bytes())
+            .default_parameter(1, "float MC_ROLL_P", 6.5f32.to_le_bytes())
+            .default_parameter(2, "float MC_ROLL_P", 5.0f32.to_le_bytes())
+            .default_parameter(2, "float MC_PITCH_P", 6.0f32.to_le_bytes())
+            .format("heartbeat:uint64_t timestamp")
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        let system = parsed_data.default_parameters_of(DefaultType::System);
+        assert_eq!(2, system.len());
+        assert_eq!(Some(&ParamValue::Float(6.5)), system.get("MC_ROLL_P"));
+        assert_eq!(None, system.get("MC_PITCH_P"));
+        let airframe = parsed_data.default_parameters_of(DefaultType::Airframe);
+        assert_eq!(3, airframe.len());
+        assert_eq!(Some(&ParamValue::Int32(0)), airframe.get("SYS_AUTOSTART"));
+        assert_eq!(Some(&ParamValue::Float(5.0)), airframe.get("MC_ROLL_P"));
+        assert_eq!(Some(&ParamValue::Float(6.0)), airframe.get("MC_PITCH_P"));
+    }
+
+    #[test]
+    fn merges_effective_parameters() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let log = TestLog::new()
+            .default_parameter(3, "int32_t SYS_AUTOSTART", 0i32.to_le_bytes())
+            .default_parameter(3, "float MC_ROLL_P", 6.5f32.to_le_bytes())
+            .default_parameter(1, "float MC_PITCH_P", 5.5f32.to_le_bytes())
+            .default_parameter(2, "float MC_PITCH_P", 6.0f32.to_le_bytes())
+            .parameter("int32_t SYS_AUTOSTART", 4001i32.to_le_bytes())
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat")
+            .data(0, &1u64.to_le_bytes())
+            .parameter("float MC_ROLL_P", 7.0f32.to_le_bytes())
+            .bytes();
+        let parsed_data = read_from_reader(log.as_slice()).unwrap();
+        let parameters = parsed_data.effective_parameters();
+        assert_eq!(3, parameters.len());
+        assert_eq!(
+            Some(&ParamValue::Int32(4001)),
+            parameters.get("SYS_AUTOSTART")
+        );
+        assert_eq!(Some(&ParamValue::Float(7.0)), parameters.get("MC_ROLL_P"));
+        // Only in the defaults
+        assert_eq!(Some(&ParamValue::Float(6.0)), parameters.get("MC_PITCH_P"));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn reads_files_in_parallel() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let results = read_files(&[&filename, &filename]);
+        assert_eq!(2, results.len());
+        let first = results[0].as_ref().unwrap();
+        let second = results[1].as_ref().unwrap();
+        assert_eq!(first.formats.len(), second.formats.len());
+        assert_eq!(first.messages.len(), second.messages.len());
+        for name in first.messages.keys() {
+            assert_eq!(
+                first.timestamps(name, MultiId::new(0)),
+                second.timestamps(name, MultiId::new(0))
+            );
+        }
+        assert!(read_files(&["no_such_file.ulg"])[0].is_err());
+    }
+
+    #[test]
+    fn reads_first_sample_of_every_instance() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let samples = read_first_samples(&filename).unwrap();
+        let parsed_data = read_file(&filename).unwrap();
+        let instance_count: usize = parsed_data.messages.values().map(HashMap::len).sum();
+        assert_eq!(instance_count, samples.len());
+        for ((message_name, multi_id), row) in &samples {
+            let fields = &parsed_data.messages[message_name][multi_id];
+            assert_eq!(fields.len(), row.len());
+        }
+        let esc_status = &samples[&("esc_status".to_string(), MultiId::new(0))];
+        match esc_status["timestamp"] {
+            FlattenedFieldValue::UInt64(timestamp) => assert_eq!(5081348405, timestamp),
+            ref other => panic!("unexpected timestamp {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hashes_content_reproducibly() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let first = read_file(&filename).unwrap().content_hash();
+        assert_eq!(first, read_file(&filename).unwrap().content_hash());
+
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        assert_ne!(first, read_file(&filename).unwrap().content_hash());
+        assert_ne!(
+            first,
+            read_file_capped(&filename, 1).unwrap().content_hash()
+        );
+    }
+
+    #[test]
+    fn lists_varying_fields() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let varying = parsed_data.varying_fields("esc_status", MultiId::new(0));
+        assert!(varying.contains(&"esc[0].esc_rpm".to_string()));
+        assert!(varying.contains(&"esc[0].esc_current".to_string()));
+        assert!(!varying.contains(&"esc[0].esc_address".to_string()));
+        assert!(varying.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert!(!SomeVec::Float(vec![f32::NAN, f32::NAN]).varies());
+        assert!(SomeVec::Float(vec![0.0, -0.0]).varies());
+        assert!(!SomeVec::Bool(vec![]).varies());
+    }
+
+    #[test]
+    fn takes_topic_out_of_parsed_data() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut parsed_data = read_file(&filename).unwrap();
+        let topic_count = parsed_data.messages.len();
+        let esc_status = parsed_data.take_topic("esc_status").unwrap();
+        assert!(!parsed_data.messages.contains_key("esc_status"));
+        assert!(parsed_data
+            .timestamps("esc_status", MultiId::new(0))
+            .is_none());
+        assert_eq!(topic_count - 1, parsed_data.messages.len());
+        assert!(parsed_data.take_topic("esc_status").is_none());
+        assert!(parsed_data.formats.contains_key("esc_status"));
+
+        let sample_count =
+            std::thread::spawn(move || esc_status[&MultiId::new(0)]["timestamp"].len())
+                .join()
+                .unwrap();
+        assert_eq!(127, sample_count);
+    }
+
+    #[test]
+    fn reads_field_of_all_instances() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let columns = parsed_data.field_all_instances("actuator_outputs", "noutputs");
+        assert_eq!(3, columns.len());
+        for (multi_id, column) in columns {
+            let timestamps = parsed_data
+                .timestamps("actuator_outputs", multi_id)
+                .unwrap();
+            assert_eq!(timestamps.len(), column.len());
+        }
+        assert!(parsed_data
+            .field_all_instances("actuator_outputs", "no_such_field")
+            .is_empty());
+        assert!(parsed_data
+            .field_all_instances("no_such_topic", "timestamp")
+            .is_empty());
+    }
+
+    #[test]
+    fn finds_argmax_and_argmin() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let (index, value, timestamp) = parsed_data
+            .argmax("battery_status", MultiId::new(0), "current_a")
+            .unwrap();
+        assert!((value - 73.9).abs() < 0.01);
+        let timestamps = parsed_data
+            .timestamps("battery_status", MultiId::new(0))
+            .unwrap();
+        assert_eq!(timestamps[index], timestamp);
+        let fields = &parsed_data.messages["battery_status"][&MultiId::new(0)];
+        match fields["current_a"].get(index) {
+            Some(FlattenedFieldValue::Float(current)) => assert_eq!(value, current as f64),
+            _ => panic!("current_a is not a float"),
+        }
+
+        let (_, min_voltage, _) = parsed_data
+            .argmin("battery_status", MultiId::new(0), "voltage_v")
+            .unwrap();
+        assert!((min_voltage - 21.75).abs() < 0.01);
+        assert_eq!(
+            None,
+            parsed_data.argmax("battery_status", MultiId::new(0), "unknown")
+        );
+    }
+
+    #[test]
+    fn finds_index_at_time() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let timestamps = parsed_data
+            .timestamps("esc_status", MultiId::new(0))
+            .unwrap();
+        let index_at = |t_us| parsed_data.index_at_time("esc_status", MultiId::new(0), t_us);
+        let (before, after) = (timestamps[60], timestamps[61]);
+        assert_eq!(Some(60), index_at(before));
+        assert_eq!(Some(60), index_at(before + 1));
+        assert_eq!(Some(61), index_at(after - 1));
+        assert_eq!(Some(0), index_at(0));
+        assert_eq!(Some(126), index_at(u64::MAX));
+        assert_eq!(
+            None,
+            parsed_data.index_at_time("esc_status", MultiId::new(1), 0)
+        );
+    }
+
+    #[test]
+    fn finds_index_at_time_after_timestamp_reset() {
+        use crate::stream_parser::test_util::TestLog;
+
+        let mut log = TestLog::new()
+            .format("heartbeat:uint64_t timestamp")
+            .add_logged_message(0, 0, "heartbeat");
+        for timestamp in &[5000u64, 6000, 7000, 100, 1100] {
+            log = log.data(0, &timestamp.to_le_bytes());
+        }
+        let parsed_data = read_from_reader(log.bytes().as_slice()).unwrap();
+        let heartbeat = ("heartbeat", MultiId::new(0));
+        let index_at = |t_us| parsed_data.index_at_time(heartbeat.0, heartbeat.1.clone(), t_us);
+        assert_eq!(Some(3), index_at(0));
+        assert_eq!(Some(4), index_at(1000));
+        assert_eq!(Some(0), index_at(5100));
+        assert_eq!(Some(2), index_at(u64::MAX));
+        let self_join = parsed_data.join_nearest(heartbeat.clone(), heartbeat, 0);
+        assert_eq!(5, self_join.len());
+        assert!(self_join.iter().all(|(left, right)| Some(*left) == *right));
+    }
+
+    #[test]
+    fn joins_topics_on_nearest_timestamp() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let esc = ("esc_status", MultiId::new(0));
+        let gps = ("vehicle_gps_position", MultiId::new(0));
+
+        let self_join = parsed_data.join_nearest(esc.clone(), esc.clone(), 0);
+        assert_eq!(127, self_join.len());
+        assert!(self_join.iter().all(|(left, right)| Some(*left) == *right));
+
+        let tolerance_us = 50_000;
+        let joined = parsed_data.join_nearest(esc.clone(), gps.clone(), tolerance_us);
+        assert_eq!(127, joined.len());
+        let esc_timestamps = parsed_data.timestamps(esc.0, esc.1.clone()).unwrap();
+        let gps_timestamps = parsed_data.timestamps(gps.0, gps.1.clone()).unwrap();
+        assert!(joined.iter().any(|(_, right)| right.is_some()));
+        for (left, right) in joined {
+            let t_us = esc_timestamps[left];
+            let nearest_diff = gps_timestamps
+                .iter()
+                .map(|gps_t_us| gps_t_us.abs_diff(t_us))
+                .min()
+                .unwrap();
+            match right {
+                Some(right) => assert_eq!(nearest_diff, gps_timestamps[right].abs_diff(t_us)),
+                None => assert!(nearest_diff > tolerance_us),
+            }
+        }
+        assert!(parsed_data
+            .join_nearest(("no_such_topic", MultiId::new(0)), esc, 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn orders_events_by_timestamp() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let parsed_data = read_file(&filename).unwrap();
+        let events = parsed_data.events();
+        assert_eq!(
+            parsed_data.logged_strings.len()
+                + parsed_data.changed_parameters.len()
+                + parsed_data.dropouts.len(),
+            events.len()
+        );
+        assert_eq!(14, events.len());
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::ParameterChange { .. })));
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+    }
+
+    #[test]
+    fn captures_definition_bytes() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        assert_eq!(crate::ULOG_MAGIC[..], parsed_data.definition_bytes[0..7]);
+
+        // Keep only the data messages of the instance logged first
+        let mut filtered = parsed_data.definition_bytes.clone();
+        let mut kept_msg_id = None;
+        let mut kept = 0;
+        let mut raw_callback = |msg_type: MessageType, data: &[u8]| {
+            if msg_type != MessageType::Data {
+                return;
+            }
+            let msg_id = u16::parse(data);
+            if *kept_msg_id.get_or_insert(msg_id) == msg_id {
+                filtered.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                filtered.push(b'D');
+                filtered.extend_from_slice(data);
+                kept += 1;
+            }
+        };
+        let mut parser = LogParser::default();
+        parser.set_raw_message_callback(&mut raw_callback);
+        consume_reader(std::fs::File::open(&filename).unwrap(), &mut parser).unwrap();
+        drop(parser);
+
+        let reparsed = read_from_reader(&filtered[..]).unwrap();
+        assert_eq!(parsed_data.formats.len(), reparsed.formats.len());
+        assert_eq!(parsed_data.parameters.len(), reparsed.parameters.len());
+        let samples: usize = reparsed
+            .messages
+            .values()
+            .flat_map(|instances| instances.values())
+            .map(|fields| fields["timestamp"].len())
+            .sum();
+        assert_eq!(kept, samples);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn reads_log_asynchronously() {
+        let bytes: &[u8] = include_bytes!("../../tests/fixtures/sample.ulg");
+        let parsed_data = read_async(std::io::Cursor::new(bytes)).await.unwrap();
+
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let expected = read_file(&filename).unwrap();
+        assert_eq!(expected.messages.len(), parsed_data.messages.len());
+        assert!(parsed_data.messages.contains_key("vehicle_attitude"));
+    }
 }
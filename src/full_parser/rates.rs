@@ -0,0 +1,163 @@
+use crate::stream_parser::model::{DataMessage, MsgId, MultiId};
+use std::collections::HashMap;
+
+/// The running sample count of a message instance, see `TopicRateTracker`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopicRate {
+    pub message_name: String,
+    pub multi_id: MultiId,
+    /// Samples so far
+    pub count: usize,
+    /// Samples per second of log time since the previous report
+    pub rate_hz: f64,
+}
+
+// Per msg_id
+struct InstanceCount {
+    message_name: String,
+    multi_id: MultiId,
+    count: usize,
+    count_at_report: usize,
+}
+
+/// Counts the samples of every message instance while parsing, e.g. to show live topic rates
+/// during ingestion. Pass every data message to `add_sample` from the data message callback.
+///
+/// Every `interval_us` of log time, the callback receives the counts and the rates since the
+/// previous report, sorted by message name and multi_id. Only the timestamps of the samples are
+/// decoded.
+pub struct TopicRateTracker<CB: FnMut(&[TopicRate])> {
+    interval_us: u64,
+    callback: CB,
+    instances: HashMap<MsgId, InstanceCount>,
+    last_report_us: Option<u64>,
+}
+
+impl<CB: FnMut(&[TopicRate])> TopicRateTracker<CB> {
+    pub fn new(interval_us: u64, callback: CB) -> Self {
+        TopicRateTracker {
+            interval_us,
+            callback,
+            instances: HashMap::new(),
+            last_report_us: None,
+        }
+    }
+
+    pub fn add_sample(&mut self, msg: &DataMessage) {
+        let instance = self
+            .instances
+            .entry(msg.msg_id)
+            .or_insert_with(|| InstanceCount {
+                message_name: msg.flattened_format.message_name.clone(),
+                multi_id: msg.multi_id.clone(),
+                count: 0,
+                count_at_report: 0,
+            });
+        instance.count += 1;
+
+        let timestamp = match msg.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+        match self.last_report_us {
+            None => self.last_report_us = Some(timestamp),
+            Some(last_report_us)
+                if timestamp >= last_report_us.saturating_add(self.interval_us) =>
+            {
+                let rates = self.rates_since(last_report_us, timestamp);
+                (self.callback)(&rates);
+                for instance in self.instances.values_mut() {
+                    instance.count_at_report = instance.count;
+                }
+                self.last_report_us = Some(timestamp);
+            }
+            _ => {}
+        }
+    }
+
+    /// The current counts, with the rates since the last report up to `now_us`
+    pub fn rates(&self, now_us: u64) -> Vec<TopicRate> {
+        self.rates_since(self.last_report_us.unwrap_or(now_us), now_us)
+    }
+
+    fn rates_since(&self, since_us: u64, now_us: u64) -> Vec<TopicRate> {
+        let elapsed_s = now_us.saturating_sub(since_us) as f64 / 1e6;
+        let mut rates: Vec<TopicRate> = self
+            .instances
+            .values()
+            .map(|instance| TopicRate {
+                message_name: instance.message_name.clone(),
+                multi_id: instance.multi_id.clone(),
+                count: instance.count,
+                rate_hz: if elapsed_s > 0.0 {
+                    (instance.count - instance.count_at_report) as f64 / elapsed_s
+                } else {
+                    0.0
+                },
+            })
+            .collect();
+        rates.sort_by(|a, b| (&a.message_name, &a.multi_id).cmp(&(&b.message_name, &b.multi_id)));
+        rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{consume_reader, read_file};
+    use super::*;
+    use crate::stream_parser::LogParser;
+
+    #[test]
+    fn tracks_topic_rates() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut reports = 0;
+        let mut esc_status_rates = Vec::new();
+        let mut tracker = TopicRateTracker::new(1_000_000, |rates: &[TopicRate]| {
+            reports += 1;
+            let esc_status = rates.iter().find(|r| r.message_name == "esc_status");
+            esc_status_rates.extend(esc_status.map(|r| r.rate_hz));
+        });
+        let mut callback = |msg: &DataMessage| tracker.add_sample(msg);
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut callback);
+        consume_reader(std::fs::File::open(&filename).unwrap(), &mut parser).unwrap();
+        drop(parser);
+        let final_rates = tracker.rates(u64::MAX);
+        drop(tracker);
+
+        assert!(reports > 10);
+        assert!(esc_status_rates.iter().any(|rate| *rate > 0.0));
+        let parsed_data = read_file(&filename).unwrap();
+        let instance_count: usize = parsed_data.messages.values().map(HashMap::len).sum();
+        assert_eq!(instance_count, final_rates.len());
+        for rate in final_rates {
+            let fields = &parsed_data.messages[&rate.message_name][&rate.multi_id];
+            assert_eq!(
+                fields["timestamp"].len(),
+                rate.count,
+                "{}",
+                rate.message_name
+            );
+        }
+    }
+
+    #[test]
+    fn never_reports_with_huge_interval() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut reports = 0;
+        let mut tracker = TopicRateTracker::new(u64::MAX, |_: &[TopicRate]| reports += 1);
+        let mut callback = |msg: &DataMessage| tracker.add_sample(msg);
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut callback);
+        consume_reader(std::fs::File::open(&filename).unwrap(), &mut parser).unwrap();
+        drop(parser);
+        drop(tracker);
+        assert_eq!(0, reports);
+    }
+}
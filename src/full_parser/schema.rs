@@ -0,0 +1,149 @@
+use super::{FlattenedFieldType, ParsedData};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Differences between the message schemas of two logs, as computed by `schema_diff`.
+///
+/// All lists are sorted by name.
+#[derive(Debug, Default, PartialEq)]
+pub struct SchemaDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub changed: Vec<MessageSchemaDiff>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Field level differences of a message that is defined in both logs.
+#[derive(Debug, Default, PartialEq)]
+pub struct MessageSchemaDiff {
+    pub message_name: String,
+    pub added_fields: Vec<String>,
+    pub removed_fields: Vec<String>,
+    // (flattened_field_name, type in a, type in b)
+    pub retyped_fields: Vec<(String, FlattenedFieldType, FlattenedFieldType)>,
+}
+
+/// Compares the flattened message formats of two logs.
+///
+/// Fields are compared by their flattened name, so e.g. a growing array shows up as added fields.
+/// Fields are "added" if they are only in `b` and "removed" if they are only in `a`.
+pub fn schema_diff(a: &ParsedData, b: &ParsedData) -> SchemaDiff {
+    let mut diff = SchemaDiff::default();
+    let names_a: BTreeSet<&String> = a.formats.keys().collect();
+    let names_b: BTreeSet<&String> = b.formats.keys().collect();
+
+    diff.only_in_a = names_a
+        .difference(&names_b)
+        .map(|name| name.to_string())
+        .collect();
+    diff.only_in_b = names_b
+        .difference(&names_a)
+        .map(|name| name.to_string())
+        .collect();
+
+    for name in names_a.intersection(&names_b) {
+        let fields_a: BTreeMap<&str, &FlattenedFieldType> = a.formats[*name]
+            .field_iter()
+            .map(|f| (f.flattened_field_name.as_str(), &f.field_type))
+            .collect();
+        let fields_b: BTreeMap<&str, &FlattenedFieldType> = b.formats[*name]
+            .field_iter()
+            .map(|f| (f.flattened_field_name.as_str(), &f.field_type))
+            .collect();
+
+        let mut message_diff = MessageSchemaDiff {
+            message_name: name.to_string(),
+            ..Default::default()
+        };
+        for (field_name, field_type) in &fields_a {
+            match fields_b.get(field_name) {
+                None => message_diff.removed_fields.push(field_name.to_string()),
+                Some(other_type) if other_type != field_type => message_diff.retyped_fields.push((
+                    field_name.to_string(),
+                    (*field_type).clone(),
+                    (*other_type).clone(),
+                )),
+                Some(_) => (),
+            }
+        }
+        message_diff.added_fields = fields_b
+            .keys()
+            .filter(|field_name| !fields_a.contains_key(*field_name))
+            .map(|field_name| field_name.to_string())
+            .collect();
+
+        if !(message_diff.added_fields.is_empty()
+            && message_diff.removed_fields.is_empty()
+            && message_diff.retyped_fields.is_empty())
+        {
+            diff.changed.push(message_diff);
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{read_file, FlattenedFormat};
+    use super::*;
+    use crate::stream_parser::model::FlattenedField;
+
+    #[test]
+    fn diffs_modified_schema() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let a = read_file(&filename).unwrap();
+        let mut b = read_file(&filename).unwrap();
+        assert!(schema_diff(&a, &b).is_empty());
+
+        b.formats.remove("ping");
+        b.formats.insert(
+            "new_message".to_string(),
+            FlattenedFormat::new("new_message".to_string(), Vec::new(), 2).unwrap(),
+        );
+        let gps = &b.formats["vehicle_gps_position"];
+        let mut fields: Vec<FlattenedField> = gps
+            .field_iter()
+            .filter(|f| f.flattened_field_name != "hdop")
+            .cloned()
+            .collect();
+        for field in fields.iter_mut() {
+            if field.flattened_field_name == "satellites_used" {
+                field.field_type = FlattenedFieldType::Int8;
+            }
+        }
+        fields.push(FlattenedField {
+            flattened_field_name: "heading".to_string(),
+            field_type: FlattenedFieldType::Float,
+            offset: gps.size(),
+        });
+        let size = gps.size() + 4;
+        b.formats.insert(
+            "vehicle_gps_position".to_string(),
+            FlattenedFormat::new("vehicle_gps_position".to_string(), fields, size).unwrap(),
+        );
+
+        let diff = schema_diff(&a, &b);
+        assert_eq!(vec!["ping".to_string()], diff.only_in_a);
+        assert_eq!(vec!["new_message".to_string()], diff.only_in_b);
+        assert_eq!(
+            vec![MessageSchemaDiff {
+                message_name: "vehicle_gps_position".to_string(),
+                added_fields: vec!["heading".to_string()],
+                removed_fields: vec!["hdop".to_string()],
+                retyped_fields: vec![(
+                    "satellites_used".to_string(),
+                    FlattenedFieldType::UInt8,
+                    FlattenedFieldType::Int8
+                )],
+            }],
+            diff.changed
+        );
+    }
+}
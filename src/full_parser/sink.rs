@@ -0,0 +1,153 @@
+use super::consume_reader;
+use crate::stream_parser::model::{DataMessage, FlattenedFieldType, FlattenedFieldValue, MsgId};
+use crate::stream_parser::{LittleEndianParser, LogParser};
+
+/// Identifies a column: the field at `field_index` in the flattened format of the message
+/// instance with `msg_id`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct FieldId {
+    pub msg_id: MsgId,
+    pub field_index: usize,
+}
+
+macro_rules! typed_push {
+    ($( $method:ident, $type:ty, $variant:ident );*) => (
+        $(
+            fn $method(&mut self, field: FieldId, value: $type) {
+                self.push_value(field, FlattenedFieldValue::$variant(value))
+            }
+        )*
+    )
+}
+
+/// Receives the decoded field values while parsing, e.g. to fill the builders of a columnar
+/// store without the intermediate `SomeVec`s, see `read_file_into_sink`.
+///
+/// The typed methods pass the value on to `push_value` unless they are overridden.
+pub trait ColumnSink {
+    /// Called for every data message before its values are pushed. The flattened format and
+    /// multi_id of the message map the `FieldId`s to names. Returns whether to push the values.
+    fn begin_sample(&mut self, _msg: &DataMessage) -> bool {
+        true
+    }
+
+    fn push_value(&mut self, field: FieldId, value: FlattenedFieldValue);
+
+    typed_push!(
+        push_i8, i8, Int8;
+        push_u8, u8, UInt8;
+        push_i16, i16, Int16;
+        push_u16, u16, UInt16;
+        push_i32, i32, Int32;
+        push_u32, u32, UInt32;
+        push_i64, i64, Int64;
+        push_u64, u64, UInt64;
+        push_f32, f32, Float;
+        push_f64, f64, Double;
+        push_bool, bool, Bool;
+        push_char, char, Char
+    );
+}
+
+// Decodes every field of the sample straight into the typed push method of the sink.
+pub(super) fn push_sample<S: ColumnSink + ?Sized>(sink: &mut S, msg: &DataMessage) {
+    if !sink.begin_sample(msg) {
+        return;
+    }
+    for (field_index, field) in msg.flattened_format.fields.iter().enumerate() {
+        let id = FieldId {
+            msg_id: msg.msg_id,
+            field_index,
+        };
+        let data = &msg.data[field.offset as usize..];
+        match field.field_type {
+            FlattenedFieldType::Int8 => sink.push_i8(id, i8::parse(data)),
+            FlattenedFieldType::UInt8 => sink.push_u8(id, u8::parse(data)),
+            FlattenedFieldType::Int16 => sink.push_i16(id, i16::parse(data)),
+            FlattenedFieldType::UInt16 => sink.push_u16(id, u16::parse(data)),
+            FlattenedFieldType::Int32 => sink.push_i32(id, i32::parse(data)),
+            FlattenedFieldType::UInt32 => sink.push_u32(id, u32::parse(data)),
+            FlattenedFieldType::Int64 => sink.push_i64(id, i64::parse(data)),
+            FlattenedFieldType::UInt64 => sink.push_u64(id, u64::parse(data)),
+            FlattenedFieldType::Float => sink.push_f32(id, f32::parse(data)),
+            FlattenedFieldType::Double => sink.push_f64(id, f64::parse(data)),
+            FlattenedFieldType::Bool => sink.push_bool(id, bool::parse(data)),
+            FlattenedFieldType::Char => sink.push_char(id, char::parse(data)),
+        }
+    }
+}
+
+/// Reads all data messages of a log into the sink, without storing anything else.
+pub fn read_file_into_sink<S: ColumnSink>(
+    file_path: &str,
+    sink: &mut S,
+) -> Result<(), std::io::Error> {
+    let mut callback = |msg: &DataMessage| push_sample(sink, msg);
+    let mut parser = LogParser::default();
+    parser.set_data_message_callback(&mut callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct CountingSink {
+        names: HashMap<FieldId, String>,
+        counts: HashMap<String, usize>,
+        floats: usize,
+    }
+
+    impl ColumnSink for CountingSink {
+        fn begin_sample(&mut self, msg: &DataMessage) -> bool {
+            for (field_index, field) in msg.flattened_format.fields.iter().enumerate() {
+                let id = FieldId {
+                    msg_id: msg.msg_id,
+                    field_index,
+                };
+                self.names.entry(id).or_insert_with(|| {
+                    format!(
+                        "{}.{}",
+                        msg.flattened_format.message_name, field.flattened_field_name
+                    )
+                });
+            }
+            true
+        }
+
+        fn push_value(&mut self, field: FieldId, _value: FlattenedFieldValue) {
+            *self.counts.entry(self.names[&field].clone()).or_insert(0) += 1;
+        }
+
+        fn push_f32(&mut self, field: FieldId, _value: f32) {
+            self.floats += 1;
+            *self.counts.entry(self.names[&field].clone()).or_insert(0) += 1;
+        }
+    }
+
+    #[test]
+    fn pushes_into_custom_sink() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut sink = CountingSink::default();
+        read_file_into_sink(&filename, &mut sink).unwrap();
+        assert_eq!(260, sink.counts["vehicle_gps_position.timestamp"]);
+        assert_eq!(260, sink.counts["vehicle_gps_position.lat"]);
+        assert_eq!(260, sink.counts["vehicle_gps_position.eph"]);
+        assert!(sink.floats > 0);
+
+        let parsed_data = super::super::read_file(&filename).unwrap();
+        let gps_fields =
+            &parsed_data.messages["vehicle_gps_position"][&super::super::MultiId::new(0)];
+        let sink_fields = sink
+            .counts
+            .keys()
+            .filter(|name| name.starts_with("vehicle_gps_position."))
+            .count();
+        assert_eq!(gps_fields.len(), sink_fields);
+    }
+}
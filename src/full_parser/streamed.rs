@@ -0,0 +1,154 @@
+use super::{collect_with, LogCollector, ParsedData};
+use crate::stream_parser::LogParser;
+use std::io::Read;
+
+/// `first_message_offset` of a packet which does not contain the start of a message
+pub const NO_MESSAGE_START: u8 = 255;
+
+/// Reads a log captured from the ULog streaming transport (MAVLink `LOGGING_DATA`).
+///
+/// The input is a sequence of packets, each consisting of
+///   * `sequence: u16`, little endian, incremented with every packet
+///   * `length: u8`, the number of log bytes in this packet
+///   * `first_message_offset: u8`, where the first message starts within the data, or
+///     `NO_MESSAGE_START`
+///   * `data: [u8; length]`
+///
+/// Lost packets are detected by gaps in the sequence. The message they cut off is dropped, and
+/// parsing resumes at the first message starting in a following packet.
+pub fn read_streamed<R: Read>(reader: R) -> Result<ParsedData, std::io::Error> {
+    collect_with(LogCollector::default(), |parser| {
+        consume_packets(reader, parser)
+    })
+}
+
+fn consume_packets<R: Read>(mut reader: R, parser: &mut LogParser) -> Result<(), std::io::Error> {
+    let mut expected_sequence = None;
+    let mut resyncing = false;
+    let mut data = [0u8; 255];
+    loop {
+        let mut packet_header = [0u8; 4];
+        match reader.read_exact(&mut packet_header) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let sequence = u16::from_le_bytes([packet_header[0], packet_header[1]]);
+        let [_, _, length, first_message_offset] = packet_header;
+        if first_message_offset != NO_MESSAGE_START && first_message_offset >= length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "first message offset {} outside of packet of length {}",
+                    first_message_offset, length
+                ),
+            ));
+        }
+        let data = &mut data[..length as usize];
+        reader.read_exact(data).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "stream ended within a packet",
+            ),
+            _ => e,
+        })?;
+        if expected_sequence.is_some_and(|expected| expected != sequence) {
+            // The rest of the incomplete message was in the lost packets.
+            parser.discard_incomplete_message();
+            resyncing = true;
+        }
+        expected_sequence = Some(sequence.wrapping_add(1));
+        let data = if !resyncing {
+            &data[..]
+        } else if first_message_offset == NO_MESSAGE_START {
+            continue;
+        } else {
+            resyncing = false;
+            &data[first_message_offset as usize..]
+        };
+        parser
+            .consume_bytes(data)
+            .map_err(|e| std::io::Error::other(format!("err: {:?}", e)))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::read_file;
+    use super::*;
+
+    // Splits a log into packets of at most 249 bytes, like the PX4 mavlink logger.
+    fn frame(log: &[u8]) -> Vec<Vec<u8>> {
+        let mut message_starts = Vec::new();
+        let mut position = 16;
+        while position + 3 <= log.len() {
+            message_starts.push(position);
+            position += 3 + u16::from_le_bytes([log[position], log[position + 1]]) as usize;
+        }
+
+        let mut packets = Vec::new();
+        for (packet_index, data) in log.chunks(249).enumerate() {
+            let start = packet_index * 249;
+            let first_message_offset = message_starts
+                .iter()
+                .find(|s| **s >= start && **s < start + data.len())
+                .map(|s| (s - start) as u8)
+                .unwrap_or(NO_MESSAGE_START);
+            let mut packet = (packet_index as u16).to_le_bytes().to_vec();
+            packet.push(data.len() as u8);
+            packet.push(first_message_offset);
+            packet.extend_from_slice(data);
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[test]
+    fn reads_streamed_log() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let framed = frame(&std::fs::read(&filename).unwrap()).concat();
+        let parsed_data = read_streamed(framed.as_slice()).unwrap();
+        let expected = read_file(&filename).unwrap();
+
+        assert_eq!(expected.messages.len(), parsed_data.messages.len());
+        assert_eq!(
+            expected.timestamps("vehicle_gps_position", crate::full_parser::MultiId::new(0)),
+            parsed_data.timestamps("vehicle_gps_position", crate::full_parser::MultiId::new(0))
+        );
+        assert_eq!(expected.parameters, parsed_data.parameters);
+    }
+
+    #[test]
+    fn resyncs_after_lost_packet() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut packets = frame(&std::fs::read(&filename).unwrap());
+        // Lose a packet in the data section, which cuts off at least one message
+        let lost_packet = packets.len() / 2;
+        packets.remove(lost_packet);
+        let parsed_data = read_streamed(packets.concat().as_slice()).unwrap();
+        let expected = read_file(&filename).unwrap();
+
+        assert_eq!(expected.messages.len(), parsed_data.messages.len());
+        let sample_count = |parsed_data: &ParsedData| -> usize {
+            parsed_data
+                .messages
+                .values()
+                .flat_map(|instances| instances.values())
+                .map(|fields| fields.values().next().map_or(0, |values| values.len()))
+                .sum()
+        };
+        let lost_samples = sample_count(&expected) - sample_count(&parsed_data);
+        assert!((1..=249 / 3).contains(&lost_samples), "{}", lost_samples);
+    }
+
+    #[test]
+    fn rejects_invalid_offset() {
+        let framed = [0u8, 0, 2, 2, 0, 0];
+        assert!(read_streamed(&framed[..]).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use super::consume_reader;
+use crate::stream_parser::model::{DataMessage, DropoutMessage, MessageType, TimestampFieldType};
+use crate::stream_parser::LogParser;
+use std::collections::HashMap;
+
+/// Overview of a log, see `summarize`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSummary {
+    /// The ULog file format version
+    pub version: u8,
+    /// The logging start time in microseconds
+    pub start_timestamp: u64,
+    /// Time between the first and the last data sample, in microseconds
+    pub duration_us: u64,
+    /// Number of data samples per message name, summed over all multi_ids
+    pub topic_counts: HashMap<String, usize>,
+    pub dropout_total_ms: u64,
+}
+
+/// Reads the overview of a log in a single pass, without decoding or storing any field data.
+///
+/// Only the `uint64_t` timestamps are used for the duration.
+pub fn summarize(file_path: &str) -> Result<LogSummary, std::io::Error> {
+    let mut topic_counts: HashMap<String, usize> = HashMap::new();
+    let mut time_span: Option<(u64, u64)> = None;
+    let mut dropout_total_ms = 0u64;
+
+    let mut data_callback = |msg: &DataMessage| {
+        let format = msg.flattened_format;
+        if let Some(count) = topic_counts.get_mut(format.message_name()) {
+            *count += 1;
+        } else {
+            topic_counts.insert(format.message_name().to_string(), 1);
+        }
+        let timestamp = match &format.timestamp_field {
+            Some(field) if field.field_type == TimestampFieldType::UInt64 => {
+                field.parse_timestamp(msg.data)
+            }
+            _ => None,
+        };
+        if let Some(timestamp) = timestamp {
+            time_span = Some(match time_span {
+                Some((first, last)) => (first.min(timestamp), last.max(timestamp)),
+                None => (timestamp, timestamp),
+            });
+        }
+    };
+    let mut dropout_callback = |msg: &DropoutMessage| {
+        dropout_total_ms += msg.duration_ms as u64;
+    };
+    let mut parser = LogParser::default();
+    parser.set_data_message_callback(&mut data_callback);
+    parser.set_dropout_message_callback(&mut dropout_callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+    let version = parser.version();
+    let start_timestamp = parser.start_timestamp();
+    drop(parser);
+
+    Ok(LogSummary {
+        version,
+        start_timestamp,
+        duration_us: time_span.map_or(0, |(first, last)| last - first),
+        topic_counts,
+        dropout_total_ms,
+    })
+}
+
+/// Counts the messages of every type in a log, keyed by the ULog type byte, e.g. 'D' for data.
+///
+/// Messages of unknown types are counted under '?'.
+pub fn message_type_histogram(file_path: &str) -> Result<HashMap<char, usize>, std::io::Error> {
+    let mut histogram = HashMap::new();
+    let mut raw_callback = |msg_type: MessageType, _: &[u8]| {
+        *histogram
+            .entry(msg_type.type_char().unwrap_or('?'))
+            .or_insert(0) += 1;
+    };
+    let mut parser = LogParser::default();
+    parser.set_raw_message_callback(&mut raw_callback);
+    consume_reader(std::fs::File::open(file_path)?, &mut parser)?;
+    drop(parser);
+    Ok(histogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_log() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let summary = summarize(&filename).unwrap();
+        assert_eq!(0, summary.version);
+        assert_eq!(112500176, summary.start_timestamp);
+        assert_eq!(179423748, summary.duration_us);
+        assert_eq!(13, summary.topic_counts.len());
+        assert_eq!(17070, summary.topic_counts["sensor_combined"]);
+        assert_eq!(1, summary.topic_counts["commander_state"]);
+        assert_eq!(26 + 31 + 62, summary.dropout_total_ms);
+
+        let parsed_data = super::super::read_file(&filename).unwrap();
+        let timestamps = parsed_data
+            .timestamps("vehicle_status", super::super::MultiId::new(0))
+            .unwrap();
+        assert_eq!(summary.topic_counts["vehicle_status"], timestamps.len());
+    }
+
+    #[test]
+    fn counts_message_types() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let histogram = message_type_histogram(&filename).unwrap();
+        assert_eq!(103, histogram[&'F']);
+        assert_eq!(64542, histogram[&'D']);
+        assert_eq!(4, histogram[&'O']);
+        assert!(!histogram.contains_key(&'Q'));
+        assert!(!histogram.contains_key(&'?'));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::stream_parser::model::{
+    DataMessage, DropoutMessage, MessageType, MsgId, MultiId, UlogParseError,
+};
+use crate::stream_parser::LogParser;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+/// A problem found by `validate`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationIssue {
+    /// Parsing failed, nothing after this point was checked
+    ParseError(String),
+    /// Messages which could not be parsed and were skipped, e.g. data messages whose size does not
+    /// match their format. Messages failing with the same error are counted together.
+    InvalidMessages {
+        msg_type: MessageType,
+        description: String,
+        count: usize,
+    },
+    /// The log ends within a message
+    Truncated {
+        trailing_bytes: usize,
+    },
+    Dropouts {
+        count: usize,
+        total_ms: u64,
+    },
+    /// Samples with a smaller timestamp than the previous sample of the same instance
+    TimestampResets {
+        message_name: String,
+        multi_id: MultiId,
+        count: usize,
+    },
+    /// Message formats which are neither subscribed to nor nested in another message. PX4 often
+    /// defines formats of topics it does not log, so this is informational.
+    UnusedDefinitions(Vec<String>),
+}
+
+/// The result of `validate`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether there are no issues besides dropouts and unused definitions, which are common in
+    /// regular logs
+    pub fn is_healthy(&self) -> bool {
+        self.issues.iter().all(|issue| {
+            matches!(
+                issue,
+                ValidationIssue::Dropouts { .. } | ValidationIssue::UnusedDefinitions(_)
+            )
+        })
+    }
+}
+
+/// Parses a whole log and collects everything suspicious about it, instead of stopping at the
+/// first problem. Only an IO error reading the file is returned as error.
+pub fn validate(file_path: &str) -> Result<ValidationReport, std::io::Error> {
+    validate_reader(std::fs::File::open(file_path)?)
+}
+
+fn validate_reader<R: Read>(mut f: R) -> Result<ValidationReport, std::io::Error> {
+    let mut last_timestamps: HashMap<MsgId, u64> = HashMap::new();
+    let mut timestamp_resets: HashMap<(String, MultiId), usize> = HashMap::new();
+    let mut data_callback = |msg: &DataMessage| {
+        let format = msg.flattened_format;
+        if let Some(timestamp) = msg.timestamp() {
+            if let Some(last_timestamp) = last_timestamps.insert(msg.msg_id, timestamp) {
+                if timestamp < last_timestamp {
+                    *timestamp_resets
+                        .entry((format.message_name().to_string(), msg.multi_id.clone()))
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+    };
+    let mut dropout_count = 0;
+    let mut dropout_total_ms = 0u64;
+    let mut dropout_callback = |msg: &DropoutMessage| {
+        dropout_count += 1;
+        dropout_total_ms += msg.duration_ms as u64;
+    };
+    // Message formats are used by subscribing to them, or by nesting them in other formats.
+    let mut used_formats: HashSet<String> = HashSet::new();
+    let mut raw_callback = |msg_type: MessageType, payload: &[u8]| match msg_type {
+        MessageType::Format => {
+            used_formats.extend(referenced_types(&String::from_utf8_lossy(payload)));
+        }
+        MessageType::AddLoggedMessage if payload.len() > 3 => {
+            used_formats.insert(String::from_utf8_lossy(&payload[3..]).into_owned());
+        }
+        _ => (),
+    };
+
+    let mut invalid_messages: Vec<(MessageType, String, usize)> = Vec::new();
+    let mut message_error_callback = |msg_type: MessageType, e: &UlogParseError| {
+        let same_error = invalid_messages
+            .iter_mut()
+            .find(|(other_type, description, _)| {
+                *other_type == msg_type && description == e.description()
+            });
+        match same_error {
+            Some((_, _, count)) => *count += 1,
+            None => invalid_messages.push((msg_type, e.description().to_string(), 1)),
+        }
+    };
+
+    let mut issues = Vec::new();
+    let mut parser = LogParser::default();
+    parser.set_keep_timestamp_resets(true);
+    parser.set_data_message_callback(&mut data_callback);
+    parser.set_dropout_message_callback(&mut dropout_callback);
+    parser.set_raw_message_callback(&mut raw_callback);
+    parser.set_message_error_callback(&mut message_error_callback);
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let num_bytes_read = f.read(&mut buf)?;
+        if num_bytes_read == 0 {
+            if parser.leftover_len() > 0 {
+                issues.push(ValidationIssue::Truncated {
+                    trailing_bytes: parser.leftover_len(),
+                });
+            }
+            break;
+        }
+        if let Err(e) = parser.consume_bytes(&buf[..num_bytes_read]) {
+            issues.push(ValidationIssue::ParseError(e.description().to_string()));
+            break;
+        }
+    }
+    let mut defined_messages: Vec<String> = parser
+        .get_final_data_format()
+        .take_flattened_formats()
+        .into_keys()
+        .collect();
+
+    for (msg_type, description, count) in invalid_messages {
+        issues.push(ValidationIssue::InvalidMessages {
+            msg_type,
+            description,
+            count,
+        });
+    }
+    if dropout_count > 0 {
+        issues.push(ValidationIssue::Dropouts {
+            count: dropout_count,
+            total_ms: dropout_total_ms,
+        });
+    }
+    let mut timestamp_resets: Vec<_> = timestamp_resets.into_iter().collect();
+    timestamp_resets.sort();
+    for ((message_name, multi_id), count) in timestamp_resets {
+        issues.push(ValidationIssue::TimestampResets {
+            message_name,
+            multi_id,
+            count,
+        });
+    }
+    defined_messages.retain(|message_name| !used_formats.contains(message_name));
+    if !defined_messages.is_empty() {
+        defined_messages.sort();
+        issues.push(ValidationIssue::UnusedDefinitions(defined_messages));
+    }
+    Ok(ValidationReport { issues })
+}
+
+// The type names used in a format definition like `name:uint64_t timestamp;other[2] values;`
+fn referenced_types(format: &str) -> Vec<String> {
+    let fields = format.split_once(':').map_or("", |(_, fields)| fields);
+    fields
+        .split(';')
+        .filter_map(|field| field.split_whitespace().next())
+        .map(|field_type| field_type.split('[').next().unwrap_or("").to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_parser::test_util::TestLog;
+
+    #[test]
+    fn reports_issues_of_flawed_log() {
+        let mut log = TestLog::new()
+            .format("unused:uint64_t timestamp")
+            .format("inner:float x")
+            .format("outer:uint64_t timestamp;inner[2] values")
+            .add_logged_message(0, 0, "outer");
+        for timestamp in &[100u64, 200, 50, 150] {
+            let mut payload = timestamp.to_le_bytes().to_vec();
+            payload.extend_from_slice(&[0; 8]);
+            log = log.data(0, &payload);
+        }
+        // Data messages which do not match their format are skipped
+        log = log
+            .data(0, &[0; 4])
+            .data(0, &[0; 4])
+            .data(7, &250u64.to_le_bytes());
+        let mut bytes = log.message(b'O', &25u16.to_le_bytes()).bytes();
+        // A data message cut off after its header
+        bytes.extend_from_slice(&[18, 0, b'D', 0]);
+
+        let mut report = validate_reader(bytes.as_slice()).unwrap();
+        assert!(!report.is_healthy());
+        let invalid_messages: Vec<(MessageType, usize)> = report
+            .issues
+            .iter()
+            .filter_map(|issue| match issue {
+                ValidationIssue::InvalidMessages {
+                    msg_type, count, ..
+                } => Some((*msg_type, *count)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![(MessageType::Data, 2), (MessageType::Data, 1)],
+            invalid_messages
+        );
+        match &report.issues[1] {
+            ValidationIssue::InvalidMessages { description, .. } => {
+                assert!(description.contains("outer has 6 bytes"), "{}", description)
+            }
+            issue => panic!("unexpected issue {:?}", issue),
+        }
+        report
+            .issues
+            .retain(|issue| !matches!(issue, ValidationIssue::InvalidMessages { .. }));
+        assert_eq!(
+            vec![
+                ValidationIssue::Truncated { trailing_bytes: 4 },
+                ValidationIssue::Dropouts {
+                    count: 1,
+                    total_ms: 25
+                },
+                ValidationIssue::TimestampResets {
+                    message_name: "outer".to_string(),
+                    multi_id: MultiId::new(0),
+                    count: 1
+                },
+                ValidationIssue::UnusedDefinitions(vec!["unused".to_string()]),
+            ],
+            report.issues
+        );
+    }
+
+    #[test]
+    fn accepts_regular_log() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        assert!(validate(&filename).unwrap().is_healthy());
+    }
+
+    #[test]
+    fn reports_parse_error() {
+        let report = validate_reader(&b"not a ulog file at all"[..]).unwrap();
+        match report.issues.as_slice() {
+            [ValidationIssue::ParseError(_)] => {}
+            issues => panic!("unexpected issues {:?}", issues),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use super::ParsedData;
+use crate::stream_parser::model::{
+    flattened_size, FlattenedFieldType, FlattenedFieldValue, FlattenedFormat, MultiId,
+};
+use crate::ULOG_MAGIC;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Write};
+
+macro_rules! value_bytes_matcher {
+    ($value:ident, $( $type:tt ),*) => (
+        match $value {
+            $(FlattenedFieldValue::$type(v) => {
+                (FlattenedFieldType::$type, v.to_le_bytes().to_vec())
+            },)+
+            FlattenedFieldValue::Bool(v) => (FlattenedFieldType::Bool, vec![*v as u8]),
+            FlattenedFieldValue::Char(v) => (FlattenedFieldType::Char, vec![*v as u8]),
+        }
+    )
+}
+
+// The type and the little endian bytes of the value, as stored in a data message.
+fn encode_value(value: &FlattenedFieldValue) -> (FlattenedFieldType, Vec<u8>) {
+    value_bytes_matcher!(
+        value, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float, Double
+    )
+}
+
+fn write_message<W: Write>(out: &mut W, msg_type: u8, payload: &[u8]) -> Result<(), Error> {
+    if payload.len() > u16::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "message too large"));
+    }
+    out.write_all(&(payload.len() as u16).to_le_bytes())?;
+    out.write_all(&[msg_type])?;
+    out.write_all(payload)
+}
+
+// Writes the header and, for version 1 and up, the flag bits without any flags set.
+fn write_header<W: Write>(out: &mut W, version: u8, start_timestamp: u64) -> Result<(), Error> {
+    out.write_all(&ULOG_MAGIC)?;
+    out.write_all(&[version])?;
+    out.write_all(&start_timestamp.to_le_bytes())?;
+    if version > 0 {
+        write_message(out, b'B', &[0; 40])?;
+    }
+    Ok(())
+}
+
+// The format string for the flattened fields, with runs of `name[0]`, `name[1]`, ... written as
+// arrays and the gaps between fields filled with padding, so the offsets stay the same.
+fn format_definition(format: &FlattenedFormat) -> String {
+    let fields = &format.fields;
+    let mut result = format!("{}:", format.message_name);
+    let mut offset = 2;
+    let mut padding_count = 0;
+    let mut i = 0;
+    while i < fields.len() {
+        let field = &fields[i];
+        if field.offset as usize > offset {
+            result += &format!(
+                "uint8_t[{}] _padding{};",
+                field.offset as usize - offset,
+                padding_count
+            );
+            padding_count += 1;
+        }
+        let type_name = field.field_type.type_name();
+        let size = field.field_type.size() as usize;
+        let mut array_len = 0;
+        if let Some(base_name) = field.flattened_field_name.strip_suffix("[0]") {
+            while let Some(next) = fields.get(i + array_len) {
+                if next.field_type != field.field_type
+                    || next.offset as usize != field.offset as usize + array_len * size
+                    || next.flattened_field_name != format!("{}[{}]", base_name, array_len)
+                {
+                    break;
+                }
+                array_len += 1;
+            }
+            result += &format!("{}[{}] {};", type_name, array_len, base_name);
+        } else {
+            result += &format!("{} {};", type_name, field.flattened_field_name);
+        }
+        offset = field.offset as usize + std::cmp::max(array_len, 1) * size;
+        i += std::cmp::max(array_len, 1);
+    }
+    result
+}
+
+/// Writes the selected topics of a parsed log as a new, smaller ULog: their definitions,
+/// subscriptions and data, ordered by timestamp. Parameters, info and logged strings are not
+/// written.
+///
+/// Fails with `InvalidInput` for topics which are not defined in the log.
+pub fn write_ulog_subset<W: Write>(
+    data: &ParsedData,
+    topics: &[&str],
+    out: &mut W,
+) -> Result<(), Error> {
+    let mut formats = Vec::new();
+    for topic in topics {
+        match data.formats.get(*topic) {
+            Some(format) => formats.push(format),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("topic {} is not defined in the log", topic),
+                ))
+            }
+        }
+    }
+    formats.sort_by(|a, b| a.message_name.cmp(&b.message_name));
+    formats.dedup_by(|a, b| a.message_name == b.message_name);
+
+    write_header(out, data.version, data.start_timestamp)?;
+    for format in &formats {
+        write_message(out, b'F', format_definition(format).as_bytes())?;
+    }
+
+    // (timestamp, bytes including the msg_id) of every sample
+    let mut samples = Vec::new();
+    let mut msg_id: u16 = 0;
+    for format in &formats {
+        let instances = match data.messages.get(&format.message_name) {
+            Some(instances) => instances,
+            None => continue,
+        };
+        let mut multi_ids: Vec<_> = instances.keys().collect();
+        multi_ids.sort_by_key(|multi_id| multi_id.value());
+        for multi_id in multi_ids {
+            let columns = &instances[multi_id];
+            let mut subscription = vec![multi_id.value()];
+            subscription.extend_from_slice(&msg_id.to_le_bytes());
+            subscription.extend_from_slice(format.message_name.as_bytes());
+            write_message(out, b'A', &subscription)?;
+
+            let sample_count = columns.values().map(|c| c.len()).max().unwrap_or(0);
+            let timestamps = data.timestamps(&format.message_name, multi_id.clone());
+            for index in 0..sample_count {
+                // Trailing padding is not written, so the sample ends with the last field
+                let mut sample = vec![0; flattened_size(&format.fields) as usize];
+                sample[0..2].copy_from_slice(&msg_id.to_le_bytes());
+                for field in &format.fields {
+                    let bytes = columns
+                        .get(&field.flattened_field_name)
+                        .and_then(|column| column.get(index))
+                        .map(|value| encode_value(&value).1);
+                    if let Some(bytes) = bytes {
+                        let offset = field.offset as usize;
+                        sample[offset..offset + bytes.len()].copy_from_slice(&bytes);
+                    }
+                }
+                let timestamp = timestamps.and_then(|t| t.get(index)).copied();
+                samples.push((timestamp.unwrap_or(0), sample));
+            }
+            msg_id += 1;
+        }
+    }
+
+    samples.sort_by_key(|(timestamp, _)| *timestamp);
+    for (_, sample) in samples {
+        write_message(out, b'D', &sample)?;
+    }
+    out.flush()
+}
+
+/// Builds a small ULog in memory, e.g. to test tools reading logs with this crate.
+///
+/// All messages must be defined before the first subscription. The log has no parameters or
+/// info messages.
+pub struct UlogWriter {
+    bytes: Vec<u8>,
+    field_types: HashMap<String, Vec<FlattenedFieldType>>,
+    msg_ids: HashMap<(String, MultiId), u16>,
+}
+
+impl UlogWriter {
+    /// A version 1 log, logged from `start_timestamp` in microseconds
+    pub fn new(start_timestamp: u64) -> Self {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, 1, start_timestamp).expect("writing to a Vec cannot fail");
+        UlogWriter {
+            bytes,
+            field_types: HashMap::new(),
+            msg_ids: HashMap::new(),
+        }
+    }
+
+    /// Defines a message with fields of the given names and types, usually starting with a
+    /// `uint64_t` timestamp.
+    pub fn define_message(
+        &mut self,
+        name: &str,
+        fields: &[(&str, FlattenedFieldType)],
+    ) -> Result<&mut Self, Error> {
+        if !self.msg_ids.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "messages must be defined before the first subscription",
+            ));
+        }
+        if self.field_types.contains_key(name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("message {} is already defined", name),
+            ));
+        }
+        let mut format = format!("{}:", name);
+        for (field_name, field_type) in fields {
+            format += &format!("{} {};", field_type.type_name(), field_name);
+        }
+        write_message(&mut self.bytes, b'F', format.as_bytes())?;
+        let types = fields.iter().map(|(_, field_type)| field_type.clone());
+        self.field_types.insert(name.to_string(), types.collect());
+        Ok(self)
+    }
+
+    /// Subscribes an instance of a defined message, which is needed to write its data.
+    pub fn add_subscription(&mut self, name: &str, multi_id: MultiId) -> Result<&mut Self, Error> {
+        if !self.field_types.contains_key(name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("message {} is not defined", name),
+            ));
+        }
+        let msg_id = self.msg_ids.len() as u16;
+        let mut subscription = vec![multi_id.value()];
+        subscription.extend_from_slice(&msg_id.to_le_bytes());
+        subscription.extend_from_slice(name.as_bytes());
+        write_message(&mut self.bytes, b'A', &subscription)?;
+        self.msg_ids.insert((name.to_string(), multi_id), msg_id);
+        Ok(self)
+    }
+
+    /// Writes a sample of a subscribed instance, with the values in the order of the fields.
+    pub fn write_data(
+        &mut self,
+        name: &str,
+        multi_id: MultiId,
+        row: &[FlattenedFieldValue],
+    ) -> Result<&mut Self, Error> {
+        let msg_id = match self.msg_ids.get(&(name.to_string(), multi_id)) {
+            Some(msg_id) => *msg_id,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("message {} is not subscribed", name),
+                ))
+            }
+        };
+        let field_types = &self.field_types[name];
+        let mut sample = msg_id.to_le_bytes().to_vec();
+        let mut types = Vec::new();
+        for value in row {
+            let (field_type, bytes) = encode_value(value);
+            types.push(field_type);
+            sample.extend_from_slice(&bytes);
+        }
+        if &types != field_types {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("values {:?} do not match the fields of {}", row, name),
+            ));
+        }
+        write_message(&mut self.bytes, b'D', &sample)?;
+        Ok(self)
+    }
+
+    /// The bytes of the log
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{read_file, read_from_reader, MultiId};
+    use super::*;
+
+    #[test]
+    fn writes_one_topic_subset() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = read_file(&filename).unwrap();
+        let mut bytes = Vec::new();
+        write_ulog_subset(&parsed_data, &["esc_status"], &mut bytes).unwrap();
+
+        let subset = read_from_reader(&bytes[..]).unwrap();
+        assert_eq!(
+            vec!["esc_status"],
+            subset.messages.keys().collect::<Vec<_>>()
+        );
+        let original = &parsed_data.messages["esc_status"][&MultiId::new(0)];
+        let written = &subset.messages["esc_status"][&MultiId::new(0)];
+        assert_eq!(original.len(), written.len());
+        for (name, column) in original {
+            assert_eq!(column.len(), written[name].len(), "{}", name);
+            for index in 0..column.len() {
+                assert_eq!(
+                    format!("{:?}", column.get(index)),
+                    format!("{:?}", written[name].get(index))
+                );
+            }
+        }
+        assert!(write_ulog_subset(&parsed_data, &["no_such_topic"], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn builds_synthetic_log() {
+        let mut writer = UlogWriter::new(100);
+        writer
+            .define_message(
+                "sensor",
+                &[
+                    ("timestamp", FlattenedFieldType::UInt64),
+                    ("value", FlattenedFieldType::Float),
+                    ("valid", FlattenedFieldType::Bool),
+                ],
+            )
+            .unwrap()
+            .add_subscription("sensor", MultiId::new(1))
+            .unwrap();
+        for (timestamp, value) in &[(1000u64, 0.5f32), (2000, 1.5)] {
+            writer
+                .write_data(
+                    "sensor",
+                    MultiId::new(1),
+                    &[
+                        FlattenedFieldValue::UInt64(*timestamp),
+                        FlattenedFieldValue::Float(*value),
+                        FlattenedFieldValue::Bool(true),
+                    ],
+                )
+                .unwrap();
+        }
+        assert!(writer
+            .write_data("sensor", MultiId::new(1), &[FlattenedFieldValue::Int8(0)])
+            .is_err());
+        assert!(writer.define_message("late", &[]).is_err());
+
+        let parsed_data = read_from_reader(&writer.finish()[..]).unwrap();
+        assert_eq!(100, parsed_data.start_timestamp);
+        assert_eq!(
+            Some(&[1000, 2000][..]),
+            parsed_data.timestamps("sensor", MultiId::new(1))
+        );
+        match parsed_data.messages["sensor"][&MultiId::new(1)]["value"].get(1) {
+            Some(FlattenedFieldValue::Float(value)) => assert_eq!(1.5, value),
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod fnv1a;
 pub mod full_parser;
 pub mod models;
 pub mod parser;
@@ -5,3 +6,25 @@ pub mod stream_parser;
 pub mod unpack;
 
 extern crate byteorder;
+
+/// The `no_std` core: little endian decoding and message framing
+pub use px4_ulog_core as core_parser;
+pub use px4_ulog_core::ULOG_MAGIC;
+
+/// Checks whether the bytes, e.g. the beginning of a file, start with the ULog magic
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+///
+/// let filename = format!("{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg", env!("CARGO_MANIFEST_DIR"));
+/// let mut first_bytes = [0; 16];
+/// std::fs::File::open(&filename).unwrap().read_exact(&mut first_bytes).unwrap();
+/// assert_eq!(px4_ulog::ULOG_MAGIC, first_bytes[..7]);
+/// assert!(px4_ulog::is_ulog_bytes(&first_bytes));
+/// assert!(!px4_ulog::is_ulog_bytes(&first_bytes[..6]));
+/// assert!(!px4_ulog::is_ulog_bytes(b"not a log file"));
+/// ```
+pub fn is_ulog_bytes(bytes: &[u8]) -> bool {
+    bytes.starts_with(&ULOG_MAGIC)
+}
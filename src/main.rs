@@ -21,7 +21,7 @@ fn main() {
             for dataset in datasets.iter() {
                 println!("--------------------------");
                 for item in dataset.iter() {
-                    if filters.len() == 0 || filters.contains(item.name()) {
+                    if filters.is_empty() || filters.contains(item.name()) {
                         println!("{} at {}: {:?}", item.name(), item.index(), item.data());
                     }
                 }
@@ -1,4 +1,8 @@
 use crate::unpack;
+use std::io::{Error, Result};
+
+/// The types that `ULogDataIter` can decode
+const SUPPORTED_TYPES: [&str; 5] = ["uint64_t", "int32_t", "float", "uint8_t", "bool"];
 
 /// Container for a single data row
 #[derive(Debug)]
@@ -45,6 +49,19 @@ pub enum DataType {
     Bool(bool),
 }
 
+// Prints the inner value, floats with the precision needed to read them back.
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::UInt64(value) => value.fmt(f),
+            DataType::Int32(value) => value.fmt(f),
+            DataType::Float(value) => value.fmt(f),
+            DataType::UInt8(value) => value.fmt(f),
+            DataType::Bool(value) => value.fmt(f),
+        }
+    }
+}
+
 impl ULogData {
     pub fn new(data: Vec<u8>, formats: Vec<String>, index: u64) -> Self {
         Self {
@@ -54,6 +71,31 @@ impl ULogData {
         }
     }
 
+    /// Create a data row, checking that every format entry has a name and a type which
+    /// the iterator can decode. Empty entries are ignored.
+    ///
+    /// # Examples
+    /// ```
+    /// use px4_ulog::models::ULogData;
+    /// let formats = vec!["uint64_t timestamp".to_string(), "double x".to_string()];
+    /// assert!(ULogData::try_new(vec![0; 16], formats, 0).is_err());
+    /// ```
+    pub fn try_new(data: Vec<u8>, formats: Vec<String>, index: u64) -> Result<Self> {
+        for format in formats.iter().filter(|f| !f.is_empty()) {
+            let parts: Vec<&str> = format.split(' ').collect();
+            if parts.len() != 2 || parts[1].is_empty() {
+                return Err(Error::other(format!("invalid format entry: {}", format)));
+            }
+            if !SUPPORTED_TYPES.contains(&parts[0]) {
+                return Err(Error::other(format!(
+                    "unsupported type in format: {}",
+                    format
+                )));
+            }
+        }
+        Ok(Self::new(data, formats, index))
+    }
+
     /// Get the unformatted data for this item
     pub fn data(&self) -> &Vec<u8> {
         &self.data
@@ -88,7 +130,7 @@ impl ULogData {
     pub fn items(&self) -> Vec<String> {
         self.formats
             .iter()
-            .filter(|f| f.len() > 0 && !f.contains("_padding") && f.contains(" "))
+            .filter(|f| !f.is_empty() && !f.contains("_padding") && f.contains(" "))
             .map(|f| f.split(" ").last().unwrap().to_string())
             .collect()
     }
@@ -112,7 +154,7 @@ impl ULogData {
     /// let first_data = dataset.next().unwrap();
     /// assert_eq!(first_data.iter().count(), 23);
     /// ```
-    pub fn iter(&self) -> ULogDataIter {
+    pub fn iter(&self) -> ULogDataIter<'_> {
         ULogDataIter {
             data: self,
             format_index: 0,
@@ -258,6 +300,30 @@ mod tests {
     use std::collections::HashMap;
     use std::fs::File;
 
+    #[test]
+    fn it_validates_formats() {
+        let formats = |formats: &[&str]| formats.iter().map(|f| f.to_string()).collect();
+        assert!(ULogData::try_new(
+            vec![0; 9],
+            formats(&["uint64_t timestamp", "bool x", ""]),
+            0
+        )
+        .is_ok());
+        assert!(ULogData::try_new(vec![0; 9], formats(&["uint64_t"]), 0).is_err());
+        assert!(
+            ULogData::try_new(vec![0; 9], formats(&["uint64_t timestamp", "int8_t x"]), 0).is_err()
+        );
+    }
+
+    #[test]
+    fn it_displays_data_types() {
+        assert_eq!("375408345", DataType::UInt64(375408345).to_string());
+        assert_eq!("-741792999", DataType::Int32(-741792999).to_string());
+        assert_eq!("0.29999998", DataType::Float(0.29999998).to_string());
+        assert_eq!("3", DataType::UInt8(3).to_string());
+        assert_eq!("false", DataType::Bool(false).to_string());
+    }
+
     #[test]
     fn it_parses_the_data() {
         let filename = format!(
@@ -307,7 +373,7 @@ mod tests {
                 "fix_type" => assert_eq!(&DataType::UInt8(3), data),
                 "vel_ned_valid" => assert_eq!(&DataType::Bool(false), data),
                 "satellites_used" => assert_eq!(&DataType::UInt8(10), data),
-                x => panic!(format!("unexpected field '{}'", x)),
+                x => panic!("unexpected field '{}'", x),
             }
         }
 
@@ -8,16 +8,16 @@ use crate::models::{MessageType, ULogData, ULogMessage};
 use crate::unpack;
 
 /// A pointer to a dataset in the log file
-pub struct ULogDataset<'a> {
+pub struct ULogDataset<'a, R = File> {
     messages: Vec<ULogMessage>,
     formats: Vec<String>,
     msg_id: u16,
-    file: &'a mut File,
+    file: &'a mut R,
     name: &'a str,
 }
 
-impl<'a> ULogDataset<'a> {
-    pub fn new(messages: Vec<ULogMessage>, file: &'a mut File, name: &'a str) -> Self {
+impl<'a, R: Read + Seek> ULogDataset<'a, R> {
+    pub fn new(messages: Vec<ULogMessage>, file: &'a mut R, name: &'a str) -> Self {
         Self {
             messages,
             formats: Vec::new(),
@@ -28,9 +28,12 @@ impl<'a> ULogDataset<'a> {
     }
 }
 
-pub trait ULogDatasetSource<'a> {
+pub trait ULogDatasetSource<'a>: Sized {
     /// Get a dataset from the log file
     ///
+    /// Only the headers of all messages, the definitions and the data messages of the dataset
+    /// are read in full, e.g. to fetch only the needed byte ranges of a remote file.
+    ///
     /// # Examples
     /// ```
     /// use std::fs::File;
@@ -45,15 +48,25 @@ pub trait ULogDatasetSource<'a> {
     ///     .unwrap()
     ///     .collect();
     /// assert_eq!(gps_positions.len(), 260);
+    ///
+    /// // A message name which is never declared is an error, not an empty dataset
+    /// let error = log_file.get_dataset("vehicle_gps_positon").err().unwrap();
+    /// assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
     /// ```
-    fn get_dataset(&'a mut self, name: &'a str) -> Result<ULogDataset<'a>>;
+    fn get_dataset(&'a mut self, name: &'a str) -> Result<ULogDataset<'a, Self>>;
 
     fn get_message_names(&'a mut self) -> Result<Vec<String>>;
 }
 
-impl<'a> ULogDatasetSource<'a> for File {
-    fn get_dataset(&'a mut self, name: &'a str) -> Result<ULogDataset<'a>> {
+impl<'a, R: Read + Seek> ULogDatasetSource<'a> for R {
+    fn get_dataset(&'a mut self, name: &'a str) -> Result<ULogDataset<'a, Self>> {
         let messages: Vec<ULogMessage> = self.messages().collect();
+        if !is_declared(self, &messages, name)? {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("message {} is not declared in the log", name),
+            ));
+        }
         let set = ULogDataset::new(messages, self, name);
         Ok(set)
     }
@@ -61,34 +74,48 @@ impl<'a> ULogDatasetSource<'a> for File {
     fn get_message_names(&'a mut self) -> Result<Vec<String>> {
         let mut names = Vec::new();
         for message in self.messages().collect::<Vec<ULogMessage>>() {
-            match message.msg_type() {
-                MessageType::Format => {
-                    let (format_name, _) = parse_format(self, &message)?;
-                    names.push(format_name);
-                }
-                _ => (),
+            if message.msg_type() == MessageType::Format {
+                let (format_name, _) = parse_format(self, &message)?;
+                names.push(format_name);
             }
         }
         Ok(names)
     }
 }
 
-impl<'a> Iterator for ULogDataset<'a> {
+impl<'a, R: Read + Seek> Iterator for ULogDataset<'a, R> {
     type Item = ULogData;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let data = get_next_data(self);
+        get_next_data(self).ok()
+    }
+}
 
-        if let Ok(item) = data {
-            Some(item)
-        } else {
-            None
+// Whether a Format or AddLoggedMessage message refers to the message name
+fn is_declared<R: Read + Seek>(file: &mut R, messages: &[ULogMessage], name: &str) -> Result<bool> {
+    for message in messages {
+        let declared_name = match message.msg_type() {
+            MessageType::Format => parse_format(file, message)?.0,
+            MessageType::AddLoggedMessage => {
+                let data = read_data(file, message)?;
+                if data.len() <= 3 {
+                    continue;
+                }
+                unpack::as_str(&data[3..])?.to_string()
+            }
+            // Everything else comes after the definitions.
+            MessageType::Data => return Ok(false),
+            _ => continue,
+        };
+        if declared_name == name {
+            return Ok(true);
         }
     }
+    Ok(false)
 }
 
-fn get_next_data(dataset: &mut ULogDataset) -> Result<ULogData> {
-    while dataset.messages.len() > 0 {
+fn get_next_data<R: Read + Seek>(dataset: &mut ULogDataset<R>) -> Result<ULogData> {
+    while !dataset.messages.is_empty() {
         let message = dataset.messages.remove(0);
         match message.msg_type() {
             MessageType::Format => {
@@ -104,19 +131,16 @@ fn get_next_data(dataset: &mut ULogDataset) -> Result<ULogData> {
 
                 if message_name == dataset.name {
                     //let multi_id = data[0];
-                    let mut msg_id_data: [u8; 2] = Default::default();
-                    msg_id_data.copy_from_slice(&data[1..3]);
-                    dataset.msg_id = unpack::as_u16_le(&msg_id_data);
+                    dataset.msg_id = unpack::read_le(&data, 1)
+                        .ok_or_else(|| Error::other("add logged message is too short"))?;
                 }
             }
             MessageType::Data => {
-                let data = read_data(dataset.file, &message)?;
-                let mut msg_id_data: [u8; 2] = Default::default();
-                msg_id_data.copy_from_slice(&data[0..2]);
-
-                let data_msg_id = unpack::as_u16_le(&msg_id_data);
+                // Only the msg_id of the data of other datasets is read
+                let data_msg_id = read_msg_id(dataset.file, &message)?;
 
                 if data_msg_id == dataset.msg_id {
+                    let data = read_data(dataset.file, &message)?;
                     let ulog_data = ULogData::new(
                         data[2..].to_vec(),
                         dataset.formats.clone(),
@@ -129,31 +153,44 @@ fn get_next_data(dataset: &mut ULogDataset) -> Result<ULogData> {
             _ => (),
         }
     }
-    Err(Error::new(ErrorKind::Other, "no more data"))
+    Err(Error::other("no more data"))
+}
+
+fn read_msg_id<R: Read + Seek>(file: &mut R, message: &ULogMessage) -> Result<u16> {
+    if message.size() < 2 {
+        return Err(Error::other("data message is too short"));
+    }
+    file.seek(SeekFrom::Start(message.position()))?;
+    let mut buffer = [0; 2];
+    file.read_exact(&mut buffer)?;
+    Ok(unpack::as_u16_le(&buffer))
 }
 
-fn read_data(file: &mut File, message: &ULogMessage) -> Result<Vec<u8>> {
+fn read_data<R: Read + Seek>(file: &mut R, message: &ULogMessage) -> Result<Vec<u8>> {
     file.seek(SeekFrom::Start(message.position()))?;
     let mut handle = file.take(message.size() as u64);
     let mut buffer = Vec::new();
     let bytes = handle.read_to_end(&mut buffer)?;
 
     if bytes as u16 != message.size() {
-        return Err(Error::new(ErrorKind::Other, "unable to read message"));
+        return Err(Error::other("unable to read message"));
     }
 
     Ok(buffer)
 }
 
-fn parse_format(file: &mut File, message: &ULogMessage) -> Result<(String, Vec<String>)> {
+fn parse_format<R: Read + Seek>(
+    file: &mut R,
+    message: &ULogMessage,
+) -> Result<(String, Vec<String>)> {
     let data = read_data(file, message)?;
-    let format = std::str::from_utf8(&data)
-        .map_err(|_| Error::new(ErrorKind::Other, "format message is not a string"))?;
+    let format =
+        std::str::from_utf8(&data).map_err(|_| Error::other("format message is not a string"))?;
 
     let parts: Vec<&str> = format.split(":").collect();
 
     if parts.len() != 2 {
-        return Err(Error::new(ErrorKind::Other, "invalid format string"));
+        return Err(Error::other("invalid format string"));
     }
 
     let name = parts[0].to_string();
@@ -161,3 +198,58 @@ fn parse_format(file: &mut File, message: &ULogMessage) -> Result<(String, Vec<S
 
     Ok((name, types))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Counts the bytes read, like a reader fetching byte ranges of a remote file
+    struct CountingReader {
+        inner: Cursor<Vec<u8>>,
+        bytes_read: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let bytes = self.inner.read(buf)?;
+            self.bytes_read += bytes;
+            Ok(bytes)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn it_reads_a_dataset_from_a_seekable_reader() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let bytes = std::fs::read(&filename).unwrap();
+        let file_size = bytes.len();
+        let mut reader = CountingReader {
+            inner: Cursor::new(bytes),
+            bytes_read: 0,
+        };
+        let gps_positions: Vec<ULogData> = reader
+            .get_dataset("vehicle_gps_position")
+            .unwrap()
+            .collect();
+        assert_eq!(gps_positions.len(), 260);
+        assert!(reader.bytes_read < file_size / 2);
+
+        let mut log_file = File::open(&filename).unwrap();
+        let expected: Vec<ULogData> = log_file
+            .get_dataset("vehicle_gps_position")
+            .unwrap()
+            .collect();
+        for (actual, expected) in gps_positions.iter().zip(expected.iter()) {
+            assert_eq!(actual.data(), expected.data());
+        }
+    }
+}
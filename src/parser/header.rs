@@ -3,8 +3,7 @@ use std::io::prelude::*;
 use std::io::{Result, SeekFrom};
 
 use crate::unpack;
-
-const HEADER_BYTES: [u8; 7] = [85, 76, 111, 103, 1, 18, 53];
+use crate::ULOG_MAGIC;
 
 pub trait ULogHeader {
     fn is_ulog(&mut self) -> bool;
@@ -46,7 +45,7 @@ impl ULogHeader for File {
         self.seek(SeekFrom::Start(0))?;
         let mut buffer = [0; 7];
         if let Ok(bytes) = self.read(&mut buffer) {
-            Ok(bytes == buffer.len() && buffer == HEADER_BYTES)
+            Ok(bytes == buffer.len() && buffer == ULOG_MAGIC)
         } else {
             Ok(false)
         }
@@ -1,13 +1,15 @@
 use crate::models::ULogMessage;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::SeekFrom;
+use std::io::{BufReader, SeekFrom};
 
 use crate::unpack;
 
 const HEADER_SIZE: u64 = 16;
 
-pub trait ULogMessageSource {
+/// Reads the messages of a log from a file or any other seekable source, e.g. a reader which
+/// fetches byte ranges of a remote file on demand.
+pub trait ULogMessageSource: Read + Seek + Sized {
     /// Creates an iterator that reads through every message in the log file
     ///
     /// # Examples
@@ -26,24 +28,74 @@ pub trait ULogMessageSource {
     /// assert_eq!(messages[21130].position(), 973045);
     /// assert_eq!(messages.len(), 21131);
     /// ```
-    fn messages(&mut self) -> ULogMessageIter;
+    fn messages(&mut self) -> ULogMessageIter<'_, Self>;
+
+    /// Creates an iterator like `messages`, which also reads the payload of every message while
+    /// scanning sequentially through the file, instead of seeking to it later
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::prelude::*;
+    /// use std::io::SeekFrom;
+    /// use px4_ulog::parser::message::*;
+    /// use px4_ulog::models::*;
+    ///
+    /// let filename = format!("{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg", env!("CARGO_MANIFEST_DIR"));
+    /// let mut log_file = std::fs::File::open(&filename).unwrap();
+    /// let messages: Vec<ULogMessageWithData> = log_file.messages_with_data().collect();
+    /// assert_eq!(messages.len(), 21131);
+    /// assert_eq!(messages[0].message.msg_type(), MessageType::FlagBits);
+    /// assert_eq!(messages[0].data.len(), 40);
+    ///
+    /// let lazy_messages: Vec<ULogMessage> = log_file.messages().collect();
+    /// for (eager, lazy) in messages.iter().zip(lazy_messages.iter()).step_by(1000) {
+    ///     assert_eq!(eager.message.position(), lazy.position());
+    ///     let mut data = vec![0; lazy.size() as usize];
+    ///     log_file.seek(SeekFrom::Start(lazy.position())).unwrap();
+    ///     log_file.read_exact(&mut data).unwrap();
+    ///     assert_eq!(eager.data, data);
+    /// }
+    /// ```
+    fn messages_with_data(&mut self) -> ULogMessageWithDataIter<'_, Self>;
+}
+
+/// A message together with its payload
+pub struct ULogMessageWithData {
+    pub message: ULogMessage,
+    pub data: Vec<u8>,
 }
 
-pub struct ULogMessageIter<'a> {
+pub struct ULogMessageIter<'a, R = File> {
     position: u64,
-    file: &'a mut File,
+    file: &'a mut R,
 }
 
-impl ULogMessageSource for File {
-    fn messages(&mut self) -> ULogMessageIter {
+pub struct ULogMessageWithDataIter<'a, R = File> {
+    position: u64,
+    reader: Option<BufReader<&'a mut R>>,
+}
+
+impl<R: Read + Seek> ULogMessageSource for R {
+    fn messages(&mut self) -> ULogMessageIter<'_, Self> {
         ULogMessageIter {
             position: HEADER_SIZE,
             file: self,
         }
     }
+
+    fn messages_with_data(&mut self) -> ULogMessageWithDataIter<'_, Self> {
+        let reader = match self.seek(SeekFrom::Start(HEADER_SIZE)) {
+            Ok(_) => Some(BufReader::new(self)),
+            Err(_) => None,
+        };
+        ULogMessageWithDataIter {
+            position: HEADER_SIZE,
+            reader,
+        }
+    }
 }
 
-impl<'a> Iterator for ULogMessageIter<'a> {
+impl<'a, R: Read + Seek> Iterator for ULogMessageIter<'a, R> {
     type Item = ULogMessage;
 
     fn next(&mut self) -> Option<ULogMessage> {
@@ -70,3 +122,32 @@ impl<'a> Iterator for ULogMessageIter<'a> {
         Some(ULogMessage::new(msg_type, msg_size, msg_pos))
     }
 }
+
+impl<'a, R: Read> Iterator for ULogMessageWithDataIter<'a, R> {
+    type Item = ULogMessageWithData;
+
+    fn next(&mut self) -> Option<ULogMessageWithData> {
+        let reader = self.reader.as_mut()?;
+
+        let mut buffer = [0; 3];
+        if reader.read_exact(&mut buffer).is_err() {
+            return None;
+        }
+        let msg_size = unpack::as_u16_le(&buffer[0..2]);
+        let msg_type = buffer[2];
+
+        let mut data = vec![0; msg_size as usize];
+        if reader.read_exact(&mut data).is_err() {
+            return None;
+        }
+
+        let msg_pos = self.position + 3;
+
+        self.position += msg_size as u64 + 3;
+
+        Some(ULogMessageWithData {
+            message: ULogMessage::new(msg_type, msg_size, msg_pos),
+            data,
+        })
+    }
+}
@@ -1,20 +1,24 @@
+use crate::fnv1a::Fnv1a;
 use crate::stream_parser::model::{ParseErrorType, UlogParseError};
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::io::Read;
-use std::iter::FromIterator;
 use std::ops::DerefMut;
+use std::sync::Mutex;
 
 use super::model;
 use crate::unpack;
+use px4_ulog_core::{FormatError, MessageSplitter, HEADER_LEN};
 
 use self::model::{
-    DataMessage, FlattenedField, FlattenedFieldType, FlattenedFormat, MultiId, ParameterMessage,
+    DataMessage, FlattenedField, FlattenedFieldType, FlattenedFormat, MsgId, MultiId,
+    ParameterMessage,
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 enum ParseStatus {
+    #[default]
     Beginning,
     AfterHeader,
     InDefinitions,
@@ -22,17 +26,15 @@ enum ParseStatus {
     //TODO: appends, probably InData works too
 }
 
-impl Default for ParseStatus {
-    fn default() -> Self {
-        ParseStatus::Beginning
-    }
-}
-
 #[derive(Default)]
 pub struct DataFormat {
     flattened_format: HashMap<String, FlattenedFormat>,
     // msg_id -> (flattened_format, multi_id, last_timestamp)
-    registered_messages: HashMap<u16, (FlattenedFormat, MultiId, u64)>,
+    registered_messages: HashMap<MsgId, (FlattenedFormat, MultiId, u64)>,
+    // msg_id -> size of the first data message, including the msg_id
+    observed_sizes: HashMap<MsgId, u16>,
+    // The unflattened definitions, once parsing ended
+    message_formats: HashMap<String, Vec<Field>>,
 }
 
 impl DataFormat {
@@ -45,7 +47,7 @@ impl DataFormat {
 
     fn register_msg_id(
         &mut self,
-        msg_id: u16,
+        msg_id: MsgId,
         message_name: &str,
         multi_id: u8,
     ) -> Result<(), UlogParseError> {
@@ -58,7 +60,7 @@ impl DataFormat {
                     ParseErrorType::Other,
                     &format!(
                         "duplicate registration for msg_id {:?}, initial one:\n{:#?}\nlater one:\n{:#?}",
-                        msg_id,
+                        msg_id.value(),
                         preexisting_message,
                         flattened_message
                     ),
@@ -79,29 +81,192 @@ impl DataFormat {
     // This should actually never return None
     pub fn get_message_description(
         &mut self,
-        msg_id: u16,
+        msg_id: MsgId,
     ) -> Option<&mut (FlattenedFormat, MultiId, u64)> {
         self.registered_messages.get_mut(&msg_id)
     }
+
+    /// The declared size of every subscribed message which had data, next to the size of its
+    /// first data message, as `(msg_id, declared, observed)` sorted by msg_id. Both include
+    /// the msg_id. Sizes which differ point to definitions which do not match the data.
+    pub fn size_diagnostics(&self) -> Vec<(MsgId, u16, u16)> {
+        let mut diagnostics: Vec<_> = self
+            .observed_sizes
+            .iter()
+            .filter_map(|(msg_id, observed)| {
+                let (format, _, _) = self.registered_messages.get(msg_id)?;
+                Some((*msg_id, format.size(), *observed))
+            })
+            .collect();
+        diagnostics.sort_unstable();
+        diagnostics
+    }
+
+    /// A fingerprint of all message schemas, e.g. for caching parsed schemas.
+    ///
+    /// It covers the sorted message names and their flattened field names and types. It is a 64 bit
+    /// FNV-1a hash, so the value is stable across runs and builds.
+    pub fn schema_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        let mut message_names: Vec<&String> = self.flattened_format.keys().collect();
+        message_names.sort();
+        for message_name in message_names {
+            hasher.update_str(message_name);
+            for field in self.flattened_format[message_name].field_iter() {
+                hasher.update_str(&field.flattened_field_name);
+                hasher.update_str(field.field_type.type_name());
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The names of all messages with a flattened field of exactly this name, sorted.
+    pub fn topics_with_field(&self, field_name: &str) -> Vec<&str> {
+        let mut topics: Vec<&str> = self
+            .flattened_format
+            .values()
+            .filter(|format| {
+                format
+                    .field_iter()
+                    .any(|field| field.flattened_field_name == field_name)
+            })
+            .map(|format| format.message_name())
+            .collect();
+        topics.sort_unstable();
+        topics
+    }
+
+    /// Checks the sizes of the definitions without flattening them, e.g. to validate a schema
+    /// before processing data. Fails with the sorted names of the messages whose flattened size
+    /// including the msg_id does not fit into the u16 offsets.
+    ///
+    /// The definitions are kept once parsing ended, see `LogParser::get_final_data_format`.
+    pub fn validate_sizes(&self) -> Result<(), Vec<String>> {
+        let mut oversized: Vec<String> = self
+            .message_formats
+            .keys()
+            .filter(|name| {
+                definition_size(name, &self.message_formats, false, &mut HashSet::new())
+                    .is_some_and(|size| size + 2 > u16::MAX as usize)
+            })
+            .cloned()
+            .collect();
+        if oversized.is_empty() {
+            return Ok(());
+        }
+        oversized.sort_unstable();
+        Err(oversized)
+    }
+
+    /// The flattened formats of all defined messages by name, subscribed or not
+    pub fn flattened_formats(&self) -> &HashMap<String, FlattenedFormat> {
+        &self.flattened_format
+    }
+
+    pub(crate) fn take_flattened_formats(&mut self) -> HashMap<String, FlattenedFormat> {
+        std::mem::take(&mut self.flattened_format)
+    }
+}
+
+/// Flattened formats shared between parsers, e.g. for many logs of the same firmware, so
+/// identical definitions are flattened only once. See `LogParser::set_schema_cache`.
+///
+/// The formats are keyed by the exact format definitions of a log, in sorted order.
+#[derive(Default)]
+pub struct SchemaCache {
+    schemas: Mutex<HashMap<Vec<String>, HashMap<String, FlattenedFormat>>>,
+}
+
+impl SchemaCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct schemas in the cache
+    pub fn len(&self) -> usize {
+        self.schemas.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
+type RawMessageCallback<'c> = &'c mut dyn FnMut(model::MessageType, &[u8]);
+type MessageErrorCallback<'c> = &'c mut dyn FnMut(model::MessageType, &UlogParseError);
+
 #[derive(Default)]
 pub struct LogParser<'c> {
+    raw_message_callback: Option<RawMessageCallback<'c>>,
     data_message_callback: Option<&'c mut dyn FnMut(&model::DataMessage)>,
     logged_string_message_callback: Option<&'c mut dyn FnMut(&model::LoggedStringMessage)>,
     parameter_message_callback: Option<&'c mut dyn FnMut(&model::ParameterMessage)>,
+    default_parameter_message_callback: Option<&'c mut dyn FnMut(&model::DefaultParameterMessage)>,
+    info_message_callback: Option<&'c mut dyn FnMut(&model::InfoMessage)>,
+    multiple_info_message_callback: Option<&'c mut dyn FnMut(&model::MultipleInfoMessage)>,
+    dropout_message_callback: Option<&'c mut dyn FnMut(&model::DropoutMessage)>,
+    message_error_callback: Option<MessageErrorCallback<'c>>,
     version: u8,
     timestamp: u64,
-    leftover: Vec<u8>,
+    // The bytes of an incomplete header
+    header_bytes: Vec<u8>,
+    // Splits the messages after the header
+    splitter: MessageSplitter,
     message_formats: HashMap<String, Vec<Field>>,
+    // The format messages as written in the log, the key of the schema cache
+    format_definitions: Vec<String>,
+    schema_cache: Option<&'c SchemaCache>,
     flattened_format: DataFormat,
     status: ParseStatus,
+    strict_incompat_flags: bool,
+    keep_timestamp_resets: bool,
+    capture_definition_bytes: bool,
+    scan_for_header: bool,
+    skipped_header_bytes: usize,
+    skip_unregistered_data: bool,
+    skipped_data_messages: usize,
+    accept_trailing_padding: bool,
+    lossy_names: bool,
+    lenient_parameter_types: bool,
+    // The header and all messages before the first data message, if captured
+    definition_bytes: Vec<u8>,
 }
 
-const MAX_MESSAGE_SIZE: usize = 2 + 1 + (u16::max_value() as usize);
-const HEADER_BYTES: [u8; 7] = [85, 76, 111, 103, 1, 18, 53];
+// Version 0 logs have no FlagBits message, which is optional anyway.
+const SUPPORTED_VERSIONS: [u8; 2] = [0, 1];
+// How far into the bytes the magic is searched for in header scan mode
+const HEADER_SCAN_LIMIT: usize = 4096;
+const SYNC_MAGIC: [u8; 8] = [0x2f, 0x73, 0x13, 0x20, 0x25, 0x0c, 0xbb, 0x12];
 
 impl<'c> LogParser<'c> {
+    /// Creates a parser which expects only data section messages, e.g. to recover the data of a
+    /// log with a corrupt header. The messages are decoded with `data_format`, which can be
+    /// obtained from a sibling log with `get_final_data_format`.
+    pub fn with_data_format(mut data_format: DataFormat) -> Self {
+        for (_, _, last_timestamp) in data_format.registered_messages.values_mut() {
+            *last_timestamp = 0;
+        }
+        LogParser {
+            flattened_format: data_format,
+            status: ParseStatus::InData,
+            splitter: MessageSplitter::headerless(),
+            ..Default::default()
+        }
+    }
+
+    /// Set a callback receiving every message with its raw payload, before it is decoded.
+    /// This is called in addition to the callbacks for specific message types.
+    pub fn set_raw_message_callback<CB: FnMut(model::MessageType, &[u8])>(
+        &mut self,
+        c: &'c mut CB,
+    ) {
+        self.raw_message_callback = Some(c)
+    }
+    /// Use the flattened formats of the cache for logs with the same definitions, and add the
+    /// formats of this log to it otherwise.
+    pub fn set_schema_cache(&mut self, cache: &'c SchemaCache) {
+        self.schema_cache = Some(cache);
+    }
     pub fn set_data_message_callback<CB: FnMut(&model::DataMessage)>(&mut self, c: &'c mut CB) {
         self.data_message_callback = Some(c)
     }
@@ -117,48 +282,212 @@ impl<'c> LogParser<'c> {
     ) {
         self.parameter_message_callback = Some(c)
     }
+    pub fn set_default_parameter_message_callback<CB: FnMut(&model::DefaultParameterMessage)>(
+        &mut self,
+        c: &'c mut CB,
+    ) {
+        self.default_parameter_message_callback = Some(c)
+    }
+    pub fn set_info_message_callback<CB: FnMut(&model::InfoMessage)>(&mut self, c: &'c mut CB) {
+        self.info_message_callback = Some(c)
+    }
+    pub fn set_multiple_info_message_callback<CB: FnMut(&model::MultipleInfoMessage)>(
+        &mut self,
+        c: &'c mut CB,
+    ) {
+        self.multiple_info_message_callback = Some(c)
+    }
+    pub fn set_dropout_message_callback<CB: FnMut(&model::DropoutMessage)>(
+        &mut self,
+        c: &'c mut CB,
+    ) {
+        self.dropout_message_callback = Some(c)
+    }
+    /// Set a callback receiving the errors of messages which cannot be parsed, e.g. data messages
+    /// whose size does not match their format. Such messages are skipped and parsing continues
+    /// with the next one, instead of failing. Errors of the header are still returned.
+    pub fn set_message_error_callback<CB: FnMut(model::MessageType, &UlogParseError)>(
+        &mut self,
+        c: &'c mut CB,
+    ) {
+        self.message_error_callback = Some(c)
+    }
+
+    /// Refuse logs with incompatible flag bits this parser does not know, with an
+    /// `IncompatibleFlags` error. By default such logs are parsed as far as possible.
+    pub fn set_strict_incompat_flags(&mut self, strict: bool) {
+        self.strict_incompat_flags = strict
+    }
+
+    /// Pass on data messages whose timestamp is smaller than the previous one of the same
+    /// instance, e.g. after a clock reset, and continue from the new timestamp. By default, only
+    /// messages with increasing timestamps are passed on.
+    pub fn set_keep_timestamp_resets(&mut self, keep: bool) {
+        self.keep_timestamp_resets = keep
+    }
+
+    /// Search the first few KB for the ULog magic instead of expecting it at the very beginning,
+    /// e.g. for uploads with a prepended BOM or newline. See `skipped_header_bytes`.
+    pub fn set_scan_for_header(&mut self, scan: bool) {
+        self.scan_for_header = scan
+    }
+
+    /// Number of bytes skipped before the magic in header scan mode
+    pub fn skipped_header_bytes(&self) -> usize {
+        self.skipped_header_bytes
+    }
+
+    /// Skip data messages with a msg_id which was never subscribed, e.g. because a dropout lost
+    /// the subscription, instead of failing. See `skipped_data_messages`.
+    pub fn set_skip_unregistered_data(&mut self, skip: bool) {
+        self.skip_unregistered_data = skip
+    }
+
+    /// Number of data messages skipped because their msg_id was not subscribed
+    pub fn skipped_data_messages(&self) -> usize {
+        self.skipped_data_messages
+    }
+
+    /// Also accept data messages which include the padding at the end of their definition, e.g.
+    /// appended data which keeps the padding while the main data leaves it out. The padding is
+    /// not part of the flattened format, so by default such messages have a size mismatch.
+    pub fn set_accept_trailing_padding(&mut self, accept: bool) {
+        self.accept_trailing_padding = accept
+    }
+
+    /// Decode invalid UTF-8 in format strings and message names lossily, like logged strings,
+    /// instead of failing. Invalid bytes are replaced by U+FFFD.
+    pub fn set_lossy_names(&mut self, lossy: bool) {
+        self.lossy_names = lossy
+    }
+
+    /// Pass on parameters of types other than float and int32_t with their value bytes as
+    /// `ParameterMessage::Raw`, instead of failing. Such types are not part of the specification,
+    /// but occur in some logs.
+    pub fn set_lenient_parameter_types(&mut self, lenient: bool) {
+        self.lenient_parameter_types = lenient
+    }
+
+    /// Keep the raw bytes of the header and of all messages before the first data message, e.g.
+    /// to re-emit the definitions of a log in front of filtered data, see `take_definition_bytes`.
+    pub fn set_capture_definition_bytes(&mut self, capture: bool) {
+        self.capture_definition_bytes = capture
+    }
+
+    /// The captured header and definition messages, exactly as they were consumed
+    pub fn take_definition_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.definition_bytes)
+    }
+
+    // Number of bytes of an incomplete message at the end of the consumed data
+    pub(crate) fn leftover_len(&self) -> usize {
+        self.header_bytes.len() + self.splitter.leftover_len()
+    }
+
+    /// Drops the bytes of an incomplete message at the end of the consumed bytes, e.g. when the
+    /// rest of it was lost in transmission. The next consumed bytes must start a new message.
+    pub fn discard_incomplete_message(&mut self) {
+        self.splitter.discard_leftover();
+    }
+
+    /// The ULog file format version from the header, 0 until the header was parsed
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The logging start time in microseconds from the header, 0 until the header was parsed
+    pub fn start_timestamp(&self) -> u64 {
+        self.timestamp
+    }
     pub fn consume_bytes(&mut self, mut buf: &[u8]) -> Result<(), UlogParseError> {
-        if !self.leftover.is_empty() {
-            assert!(self.leftover.len() < MAX_MESSAGE_SIZE);
-            let original_leftover_len = self.leftover.len();
-            let bytes_to_copy = std::cmp::min(buf.len(), MAX_MESSAGE_SIZE - self.leftover.len());
-            self.leftover.extend_from_slice(&buf[0..bytes_to_copy]);
-            // Make leftover accessible while self is borrowed immutably.
-            let mut leftover = Vec::new();
-            std::mem::swap(&mut leftover, &mut self.leftover);
-            let leftover_bytes_used = self.parse_single_entry(leftover.as_slice())?;
-            std::mem::swap(&mut leftover, &mut self.leftover);
-            if leftover_bytes_used == 0 {
-                // If we have no error and nothing to read within this much data, this implementation has issues.
-                assert!(self.leftover.len() < MAX_MESSAGE_SIZE);
-                self.leftover.truncate(original_leftover_len);
-                return Ok(());
-            }
-            if leftover_bytes_used < original_leftover_len {
-                // We are not done with the original leftovers, call this function again to get rid of that.
-                self.leftover.truncate(original_leftover_len);
-                self.leftover.drain(0..leftover_bytes_used);
-                return self.consume_bytes(buf);
-            }
-            // We are done reading the leftover, start reading buf at the next unconsumed index.
-            self.leftover.clear();
-            buf = &buf[(leftover_bytes_used - original_leftover_len)..buf.len()];
-        }
-        loop {
-            let num_bytes_consumed = self.parse_single_entry(&buf)?;
-            if num_bytes_consumed == 0 {
-                self.leftover.extend_from_slice(buf);
+        if self.status == ParseStatus::Beginning {
+            buf = &buf[self.consume_header(buf)?..];
+            if self.status == ParseStatus::Beginning {
                 return Ok(());
             }
-            buf = &buf[num_bytes_consumed..];
         }
+        // Make the splitter accessible while self is borrowed mutably by the callback.
+        let mut splitter = std::mem::take(&mut self.splitter);
+        let result = splitter.consume_bytes(buf, &mut |msg_type, payload| {
+            self.parse_framed_message(msg_type, payload)
+        });
+        self.splitter = splitter;
+        result
     }
 
     // Consumes self to make sure this is the final data_format.
-    pub fn get_final_data_format(self) -> DataFormat {
+    pub fn get_final_data_format(mut self) -> DataFormat {
+        self.flattened_format.message_formats = self.message_formats;
         self.flattened_format
     }
 
+    /// Ends parsing after all bytes were consumed and returns the final data format.
+    ///
+    /// Fails with a `Truncated` error if the bytes ended within a message. Definitions of a log
+    /// without a data section are flattened, so the formats are available in that case too.
+    pub fn finish(mut self) -> Result<DataFormat, UlogParseError> {
+        if self.leftover_len() > 0 {
+            return Err(UlogParseError::new(
+                ParseErrorType::Truncated,
+                &format!(
+                    "log ends within a message, {} bytes left over",
+                    self.leftover_len()
+                ),
+            ));
+        }
+        if self.status == ParseStatus::InDefinitions {
+            self.flattened_format = DataFormat::new(self.flatten_definitions()?);
+        }
+        self.flattened_format.message_formats = self.message_formats;
+        Ok(self.flattened_format)
+    }
+
+    // Parameters may come before the first definition, which starts the definitions section.
+    fn parameter_log_stage(&mut self) -> Result<model::LogStage, UlogParseError> {
+        match self.status {
+            ParseStatus::Beginning => Err(UlogParseError::new(
+                ParseErrorType::Other,
+                "parameter message encountered bad status",
+            )),
+            ParseStatus::AfterHeader => {
+                self.status = ParseStatus::InDefinitions;
+                Ok(model::LogStage::Definitions)
+            }
+            ParseStatus::InDefinitions => Ok(model::LogStage::Definitions),
+            ParseStatus::InData => Ok(model::LogStage::Data),
+        }
+    }
+
+    // Flattens the format definitions, or takes them from the schema cache if there is one.
+    fn flatten_definitions(&mut self) -> Result<HashMap<String, FlattenedFormat>, UlogParseError> {
+        let cache = match self.schema_cache {
+            Some(cache) => cache,
+            None => return flatten_format(&self.message_formats),
+        };
+        self.format_definitions.sort_unstable();
+        if let Some(formats) = cache.schemas.lock().unwrap().get(&self.format_definitions) {
+            return Ok(formats.clone());
+        }
+        let formats = flatten_format(&self.message_formats)?;
+        // Cloned, since the definitions may be flattened again, e.g. by finish after
+        // get_final_data_format
+        cache
+            .schemas
+            .lock()
+            .unwrap()
+            .insert(self.format_definitions.clone(), formats.clone());
+        Ok(formats)
+    }
+
+    // Info, multiple info and dropout messages are not needed to decode the data, so malformed
+    // ones are skipped instead of failing the parse. They are still reported to the message error
+    // callback.
+    fn skip_malformed_metadata(&mut self, msg_type: model::MessageType, e: &UlogParseError) {
+        if let Some(cb) = &mut self.message_error_callback {
+            cb(msg_type, e);
+        }
+    }
+
     fn transition_to_data_section_if_necessary(
         &mut self,
         message_type: model::MessageType,
@@ -170,45 +499,108 @@ impl<'c> LogParser<'c> {
             ));
         }
         if self.status == ParseStatus::InDefinitions {
-            self.flattened_format = DataFormat::new(flatten_format(&self.message_formats)?);
+            self.flattened_format = DataFormat::new(self.flatten_definitions()?);
             self.status = ParseStatus::InData;
         }
         Ok(())
     }
 
-    // Parses a header or a message.
-    fn parse_single_entry(&mut self, buf: &[u8]) -> Result<usize, UlogParseError> {
-        assert!(self.leftover.is_empty());
-        if self.status == ParseStatus::Beginning {
-            if buf.len() < 16 {
-                return Ok(0);
+    // Collects the header from the start of buf and parses it once complete. Returns the number
+    // of bytes of buf consumed.
+    fn consume_header(&mut self, buf: &[u8]) -> Result<usize, UlogParseError> {
+        let mut consumed = 0;
+        while self.status == ParseStatus::Beginning && consumed < buf.len() {
+            let num_bytes =
+                std::cmp::min(HEADER_LEN - self.header_bytes.len(), buf.len() - consumed);
+            self.header_bytes
+                .extend_from_slice(&buf[consumed..(consumed + num_bytes)]);
+            consumed += num_bytes;
+            if self.header_bytes.len() < HEADER_LEN {
+                break;
             }
-            if buf[0..7] != HEADER_BYTES {
-                return Err(UlogParseError::new(
-                    ParseErrorType::InvalidFile,
-                    "The header does not match the template",
-                ));
+            let header_bytes = std::mem::take(&mut self.header_bytes);
+            let header_bytes_used = self.parse_header(&header_bytes)?;
+            self.header_bytes
+                .extend_from_slice(&header_bytes[header_bytes_used..]);
+        }
+        Ok(consumed)
+    }
+
+    // Parses the header, or skips bytes before it in header scan mode. Returns the number of
+    // bytes used.
+    fn parse_header(&mut self, buf: &[u8]) -> Result<usize, UlogParseError> {
+        if buf[0..7] != crate::ULOG_MAGIC && self.scan_for_header {
+            return self.skip_to_magic(buf);
+        }
+        if buf[0..7] != crate::ULOG_MAGIC {
+            return Err(UlogParseError::new(
+                ParseErrorType::InvalidFile,
+                "The header does not match the template",
+            ));
+        }
+        if !SUPPORTED_VERSIONS.contains(&buf[7]) {
+            return Err(UlogParseError::new(
+                ParseErrorType::UnsupportedVersion,
+                &format!(
+                    "unsupported ULog version {}, supported versions are {:?}",
+                    buf[7], SUPPORTED_VERSIONS
+                ),
+            ));
+        }
+        self.version = buf[7];
+        self.timestamp = unpack::as_u64_le(&buf[8..HEADER_LEN]);
+        self.status = ParseStatus::AfterHeader;
+        self.splitter = MessageSplitter::headerless();
+        if self.capture_definition_bytes {
+            self.definition_bytes.extend_from_slice(&buf[0..HEADER_LEN]);
+        }
+        Ok(HEADER_LEN)
+    }
+
+    // Parses a message split off by the splitter.
+    fn parse_framed_message(&mut self, msg_type: u8, payload: &[u8]) -> Result<(), UlogParseError> {
+        if self.capture_definition_bytes {
+            if msg_type == b'D' {
+                self.capture_definition_bytes = false;
+            } else {
+                self.definition_bytes
+                    .extend_from_slice(&(payload.len() as u16).to_le_bytes());
+                self.definition_bytes.push(msg_type);
+                self.definition_bytes.extend_from_slice(payload);
             }
-            self.version = buf[7];
-            self.timestamp = unpack::as_u64_le(&buf[8..16]);
-            self.status = ParseStatus::AfterHeader;
-            return Ok(16);
         }
-        if buf.len() < 3 {
-            return Ok(0);
+        let msg = model::ULogMessage::new(msg_type, payload);
+        let msg_type = msg.msg_type();
+        if let Err(e) = self.parse_message(msg) {
+            match &mut self.message_error_callback {
+                Some(cb) => cb(msg_type, &e),
+                None => return Err(e),
+            }
         }
-        let msg_size = unpack::as_u16_le(&buf[0..2]);
-        let msg_type = buf[2];
-        let consumed_len = msg_size as usize + 3;
-        if buf.len() <= consumed_len {
-            return Ok(0);
+        Ok(())
+    }
+
+    // Consumes the bytes before the magic, or all bytes which cannot be the start of the magic.
+    fn skip_to_magic(&mut self, buf: &[u8]) -> Result<usize, UlogParseError> {
+        let magic_len = crate::ULOG_MAGIC.len();
+        let skipped = buf
+            .windows(magic_len)
+            .position(|window| window == crate::ULOG_MAGIC)
+            .unwrap_or(buf.len() + 1 - magic_len);
+        if self.skipped_header_bytes + skipped > HEADER_SCAN_LIMIT {
+            return Err(UlogParseError::new(
+                ParseErrorType::InvalidFile,
+                &format!("no ULog magic within the first {} bytes", HEADER_SCAN_LIMIT),
+            ));
         }
-        let msg = model::ULogMessage::new(msg_type, &buf[3..(3 + msg_size as usize)]);
-        self.parse_message(msg)?;
-        Ok(consumed_len)
+        self.skipped_header_bytes += skipped;
+        Ok(skipped)
     }
 
     fn parse_message(&mut self, msg: model::ULogMessage) -> Result<(), UlogParseError> {
+        if let Some(cb) = &mut self.raw_message_callback {
+            cb(msg.msg_type(), msg.data());
+        }
         match msg.msg_type() {
             model::MessageType::FlagBits => {
                 if self.status != ParseStatus::AfterHeader {
@@ -219,29 +611,41 @@ impl<'c> LogParser<'c> {
                 }
                 let flag_bits = parse_flag_bits(&msg)?;
 
-                // Check for incompatible flag bits. If there's any unknown bits set, we cannot
-                // parse the log
+                // Check for incompatible flag bits. If there's any unknown bits set, the log
+                // may not be parsed correctly, so a strict parser refuses it
                 const ULOG_INCOMPAT_FLAG0_DATA_APPENDED_MASK: u8 = 1u8 << 0;
-                if (flag_bits.incompat_flags[0] & !ULOG_INCOMPAT_FLAG0_DATA_APPENDED_MASK) != 0 {
-                    return Err(UlogParseError::new(
-                        ParseErrorType::Other,
-                        &format!("Cannot parse log, incompatible flag bits set (idx={}, value={})", 0, flag_bits.incompat_flags[0])
-                    ));
-                }
-                for flag_idx in 1..flag_bits.incompat_flags.len() {
-                    if flag_bits.incompat_flags[flag_idx] != 0 {
-                        return Err(UlogParseError::new(
-                            ParseErrorType::Other,
-                            &format!("Cannot parse log, incompatible flag bits set (idx={}, value={})", flag_idx, flag_bits.incompat_flags[flag_idx])
-                        ));
+                if self.strict_incompat_flags {
+                    for (flag_idx, flags) in flag_bits.incompat_flags.iter().enumerate() {
+                        let known_flags = if flag_idx == 0 {
+                            ULOG_INCOMPAT_FLAG0_DATA_APPENDED_MASK
+                        } else {
+                            0
+                        };
+                        if flags & !known_flags != 0 {
+                            return Err(UlogParseError::new(
+                                ParseErrorType::IncompatibleFlags,
+                                &format!(
+                                    "Cannot parse log, incompatible flag bits set (idx={}, value={})",
+                                    flag_idx, flags
+                                ),
+                            ));
+                        }
                     }
                 }
 
                 self.status = ParseStatus::InDefinitions;
             }
             model::MessageType::Format => {
-                let format = parse_format(&msg)?;
+                // Version 0 logs have no FlagBits message, which otherwise starts the definitions.
+                if self.status == ParseStatus::AfterHeader {
+                    self.status = ParseStatus::InDefinitions;
+                }
+                let format = parse_format(&msg, self.lossy_names)?;
                 let message_name = format.message_name.to_string();
+                if self.schema_cache.is_some() {
+                    self.format_definitions
+                        .push(String::from_utf8_lossy(msg.data()).into_owned());
+                }
                 if self
                     .message_formats
                     .insert(format.message_name.to_string(), format.fields)
@@ -256,71 +660,111 @@ impl<'c> LogParser<'c> {
             model::MessageType::AddLoggedMessage => {
                 self.transition_to_data_section_if_necessary(msg.msg_type())?;
                 let multi_id = msg.data[0];
-                let msg_id = unpack::as_u16_le(&msg.data[1..3]);
-                let message_name = std::str::from_utf8(&msg.data[3..]).map_err(|_| {
-                    UlogParseError::new(
-                        ParseErrorType::Other,
-                        &format!("format message is not a string {:?}", &msg.data[3..]),
-                    )
-                })?;
-                self.flattened_format
-                    .register_msg_id(msg_id, message_name, multi_id)?;
-            }
-            model::MessageType::Parameter => {
-                let log_stage = match self.status {
-                    ParseStatus::Beginning => {
-                        return Err(UlogParseError::new(
-                            ParseErrorType::Other,
-                            "parameter message encountered bad status",
-                        ));
-                    }
-                    ParseStatus::AfterHeader => {
-                        self.status = ParseStatus::InDefinitions;
-                        model::LogStage::Definitions
-                    }
-                    ParseStatus::InDefinitions => model::LogStage::Definitions,
-                    ParseStatus::InData => model::LogStage::Data,
-                };
-                let key_len = msg.data[0];
-                let value_bytes = &msg.data()[(1 + key_len as usize)..];
-                if value_bytes.len() != 4 {
-                    return Err(UlogParseError::new(
-                        ParseErrorType::Other,
-                        "parameter message with wrong size encountered",
-                    ));
-                }
-                let key =
-                    std::str::from_utf8(&msg.data()[1..(1 + key_len as usize)]).map_err(|_| {
+                let msg_id = MsgId::new(unpack::as_u16_le(&msg.data[1..3]));
+                let message_name =
+                    decode_name(&msg.data[3..], self.lossy_names).ok_or_else(|| {
                         UlogParseError::new(
                             ParseErrorType::Other,
-                            "parameter format message is not a string",
+                            &format!("format message is not a string {:?}", &msg.data[3..]),
                         )
                     })?;
-                let parts: Vec<&str> = key.split(" ").collect();
-                if parts.len() != 2 {
+                self.flattened_format
+                    .register_msg_id(msg_id, &message_name, multi_id)?;
+            }
+            model::MessageType::Parameter => {
+                let log_stage = self.parameter_log_stage()?;
+                let parameter_message =
+                    parse_parameter(msg.data(), log_stage, self.lenient_parameter_types)?;
+                if let Some(cb) = &mut self.parameter_message_callback {
+                    cb(&parameter_message);
+                }
+            }
+            model::MessageType::DefaultParameter => {
+                let log_stage = self.parameter_log_stage()?;
+                if msg.data().is_empty() {
                     return Err(UlogParseError::new(
                         ParseErrorType::Other,
-                        "parameter format message is not a string",
+                        "empty default parameter message encountered",
                     ));
                 }
-                let parameter_message = match parts[0] {
-                    "int32_t" => Ok(ParameterMessage::Int32(
-                        parts[1],
-                        unpack::as_i32_le(value_bytes),
+                let default_parameter_message = model::DefaultParameterMessage {
+                    default_types: msg.data()[0],
+                    parameter: parse_parameter(
+                        &msg.data()[1..],
                         log_stage,
-                    )),
-                    "float" => Ok(ParameterMessage::Float(
-                        parts[1],
-                        unpack::as_f32_le(value_bytes),
-                        log_stage,
-                    )),
-                    _ => Err(UlogParseError::new(
+                        self.lenient_parameter_types,
+                    )?,
+                };
+                if let Some(cb) = &mut self.default_parameter_message_callback {
+                    cb(&default_parameter_message);
+                }
+            }
+            // The magic is only found at this position if the previous message had the right size.
+            model::MessageType::Sync if !msg.data().starts_with(&SYNC_MAGIC) => {
+                return Err(UlogParseError::new(
+                    ParseErrorType::Other,
+                    &format!("sync message with wrong magic {:?}", msg.data()),
+                ));
+            }
+            model::MessageType::Info => {
+                if self.status == ParseStatus::AfterHeader {
+                    self.status = ParseStatus::InDefinitions;
+                }
+                let (value_type, key, value) = match parse_key_value(&msg) {
+                    Ok(key_value) => key_value,
+                    Err(e) => {
+                        self.skip_malformed_metadata(msg.msg_type(), &e);
+                        return Ok(());
+                    }
+                };
+                if let Some(cb) = &mut self.info_message_callback {
+                    cb(&model::InfoMessage {
+                        key,
+                        value_type,
+                        value,
+                    });
+                }
+            }
+            model::MessageType::MultipleInfo => {
+                if self.status == ParseStatus::AfterHeader {
+                    self.status = ParseStatus::InDefinitions;
+                }
+                let parsed = match msg.data().split_first() {
+                    Some((is_continued, key_value)) => {
+                        parse_key_value(&model::ULogMessage::new(b'M', key_value))
+                            .map(|key_value| (*is_continued != 0, key_value))
+                    }
+                    None => Err(UlogParseError::new(
                         ParseErrorType::Other,
-                        "parameter format message unexpected type",
+                        "multiple info message was empty",
                     )),
-                }?;
-                if let Some(cb) = &mut self.parameter_message_callback {
-                    cb(&parameter_message);
+                };
+                let (is_continued, (value_type, key, value)) = match parsed {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        self.skip_malformed_metadata(msg.msg_type(), &e);
+                        return Ok(());
+                    }
+                };
+                if let Some(cb) = &mut self.multiple_info_message_callback {
+                    cb(&model::MultipleInfoMessage {
+                        is_continued,
+                        key,
+                        value_type,
+                        value,
+                    });
+                }
+            }
+            model::MessageType::Dropout => {
+                if msg.data.len() < 2 {
+                    let e =
+                        UlogParseError::new(ParseErrorType::Other, "Dropout message was too short");
+                    self.skip_malformed_metadata(msg.msg_type(), &e);
+                    return Ok(());
+                }
+                let duration_ms = unpack::as_u16_le(&msg.data[0..2]);
+                if let Some(cb) = &mut self.dropout_message_callback {
+                    cb(&model::DropoutMessage { duration_ms });
                 }
             }
             model::MessageType::Logging => {
@@ -328,7 +772,7 @@ impl<'c> LogParser<'c> {
                 if msg.data.len() < 9 {
                     return Err(UlogParseError::new(
                         ParseErrorType::Other,
-                        &"Logged string message was too short",
+                        "Logged string message was too short",
                     ));
                 }
                 let log_level = msg.data[0];
@@ -357,32 +801,70 @@ impl<'c> LogParser<'c> {
                         "encountered data message which was too short",
                     ));
                 }
-                let msg_id = unpack::as_u16_le(&msg.data[0..2]);
+                let msg_id = MsgId::new(unpack::as_u16_le(&msg.data[0..2]));
+                if self.skip_unregistered_data
+                    && !self
+                        .flattened_format
+                        .registered_messages
+                        .contains_key(&msg_id)
+                {
+                    self.skipped_data_messages += 1;
+                    return Ok(());
+                }
+                self.flattened_format
+                    .observed_sizes
+                    .entry(msg_id)
+                    .or_insert_with(|| msg.size());
                 let (ref mut flattened_format, ref mut multi_id, ref mut last_timestamp) = self
                     .flattened_format
                     .get_message_description(msg_id)
                     .ok_or_else(|| {
                         UlogParseError::new(
                             ParseErrorType::Other,
-                            &format!("data message encountered unregistered msg_id: {}", msg_id),
+                            &format!(
+                                "data message encountered unregistered msg_id: {}",
+                                msg_id.value()
+                            ),
                         )
                     })?;
-                if flattened_format.size() != msg.size() {
-                    return Err(UlogParseError::new(
-                        ParseErrorType::Other,
-                        &format!(
-                            "data message had wrong size {}, format: {:?}",
-                            msg.size(),
-                            flattened_format
-                        ),
-                    ));
+                // Both without the msg_id, which was checked to be there above
+                let payload_size = flattened_format.payload_size() as usize;
+                let padded_size = payload_size + flattened_format.trailing_padding() as usize;
+                let data_size = msg.data().len() - 2;
+                if data_size != payload_size
+                    && !(self.accept_trailing_padding && data_size == padded_size)
+                {
+                    return Err(size_mismatch_error(flattened_format, msg.size()));
+                }
+                if flattened_format.fields.is_empty() {
+                    // Nothing to decode and no timestamp to order by, pass on the empty row.
+                    if let Some(cb) = &mut self.data_message_callback {
+                        cb(&DataMessage {
+                            msg_id,
+                            multi_id: multi_id.clone(),
+                            data: msg.data(),
+                            flattened_format,
+                        });
+                    }
+                    return Ok(());
                 }
                 let timestamp_field = flattened_format.timestamp_field.as_ref().ok_or_else(|| UlogParseError::new(
                     ParseErrorType::Other,
                     &format!("Message does not have a timestamp field {}", flattened_format.message_name),
                 ))?;
-                let current_timestamp = timestamp_field.parse_timestamp(msg.data());
-                if *last_timestamp < current_timestamp {
+                let current_timestamp =
+                    timestamp_field.parse_timestamp(msg.data()).ok_or_else(|| {
+                        UlogParseError::new(
+                            ParseErrorType::Other,
+                            &format!(
+                                "data message too short for timestamp {}",
+                                flattened_format.message_name
+                            ),
+                        )
+                    })?;
+                if *last_timestamp < current_timestamp
+                    || (self.keep_timestamp_resets && current_timestamp < *last_timestamp)
+                {
                     *last_timestamp = current_timestamp;
                     if let Some(cb) = &mut self.data_message_callback {
                         cb(&DataMessage {
@@ -449,33 +931,108 @@ pub enum MaybeRepeatedType {
 }
 
 impl MaybeRepeatedType {
-    fn from_str(written_type: &str) -> Result<Self, UlogParseError> {
-        let split: Vec<&str> = written_type.split("[").collect();
-        if split.len() == 1 {
-            return Ok(MaybeRepeatedType::Singular(DataType::from_str(
-                written_type,
-            )));
-        } else if split.len() == 2 && split[1].ends_with("]") {
-            let should_be_number = &split[1][0..(split[1].len() - 1)];
-            if let Ok(number) = should_be_number.parse::<i16>() {
-                return Ok(MaybeRepeatedType::Repeated(
-                    DataType::from_str(split[0]),
-                    number,
-                ));
-            }
+    fn from_field(field: &px4_ulog_core::FormatField) -> Self {
+        let data_type = DataType::from_str(field.type_name);
+        match field.array_len {
+            Some(array_len) => MaybeRepeatedType::Repeated(data_type, array_len),
+            None => MaybeRepeatedType::Singular(data_type),
         }
-        Err(UlogParseError::new(
+    }
+}
+
+// Names the fields which do not fit into the data, or the bytes not covered by any field.
+fn size_mismatch_error(format: &FlattenedFormat, data_size: u16) -> UlogParseError {
+    let mismatch = if data_size < format.size() {
+        let missing: Vec<&str> = format
+            .fields
+            .iter()
+            .filter(|field| field.offset.saturating_add(field.field_type.size()) > data_size)
+            .map(|field| field.flattened_field_name.as_str())
+            .collect();
+        format!("fields beyond the data: {}", missing.join(", "))
+    } else {
+        format!(
+            "{} bytes not covered by any field",
+            data_size - format.size()
+        )
+    };
+    UlogParseError::new(
+        ParseErrorType::Other,
+        &format!(
+            "data message of {} has {} bytes but the format needs {}, {}; layout: {}",
+            format.message_name,
+            data_size,
+            format.size(),
+            mismatch,
+            format.describe_layout()
+        ),
+    )
+}
+
+fn parse_parameter(
+    data: &[u8],
+    log_stage: model::LogStage,
+    lenient_types: bool,
+) -> Result<ParameterMessage<'_>, UlogParseError> {
+    let key_len = *data.first().ok_or_else(|| {
+        UlogParseError::new(ParseErrorType::Other, "empty parameter message encountered")
+    })? as usize;
+    let key_bytes = data.get(1..(1 + key_len)).ok_or_else(|| {
+        UlogParseError::new(
             ParseErrorType::Other,
-            &format!("invalid type string: {}", written_type),
-        ))
+            "parameter message shorter than its key length",
+        )
+    })?;
+    let value_bytes = &data[(1 + key_len)..];
+    let key = std::str::from_utf8(key_bytes).map_err(|_| {
+        UlogParseError::new(
+            ParseErrorType::Other,
+            "parameter format message is not a string",
+        )
+    })?;
+    let parts: Vec<&str> = key.split(" ").collect();
+    if parts.len() != 2 {
+        return Err(UlogParseError::new(
+            ParseErrorType::Other,
+            "parameter format message is not a string",
+        ));
+    }
+    let known_type = parts[0] == "int32_t" || parts[0] == "float";
+    if known_type && value_bytes.len() != 4 {
+        return Err(UlogParseError::new(
+            ParseErrorType::Other,
+            "parameter message with wrong size encountered",
+        ));
+    }
+    match parts[0] {
+        "int32_t" => Ok(ParameterMessage::Int32(
+            parts[1],
+            unpack::as_i32_le(value_bytes),
+            log_stage,
+        )),
+        "float" => Ok(ParameterMessage::Float(
+            parts[1],
+            unpack::as_f32_le(value_bytes),
+            log_stage,
+        )),
+        type_name if lenient_types => Ok(ParameterMessage::Raw(
+            parts[1],
+            type_name,
+            value_bytes,
+            log_stage,
+        )),
+        _ => Err(UlogParseError::new(
+            ParseErrorType::Other,
+            "parameter format message unexpected type",
+        )),
     }
 }
 
+// Only the incompatible flags are needed, the compatible flags and the offsets of appended data
+// are not used so far.
 #[derive(Debug)]
 struct FlagBits {
-    compat_flags: [u8; 8],
     incompat_flags: [u8; 8],
-    appended_offsets: [u64; 3],
 }
 
 fn parse_flag_bits(message: &model::ULogMessage) -> Result<FlagBits, UlogParseError> {
@@ -487,15 +1044,36 @@ fn parse_flag_bits(message: &model::ULogMessage) -> Result<FlagBits, UlogParseEr
         ));
     }
 
-    let mut compat_flags: [u8; 8] = Default::default();
-    compat_flags.copy_from_slice(&message.data[0..8]);
     let mut incompat_flags: [u8; 8] = Default::default();
     incompat_flags.copy_from_slice(&message.data[8..16]);
-    Ok(FlagBits {
-        compat_flags,
-        incompat_flags,
-        appended_offsets: [unpack::as_u64_le( & message.data[16..24]), unpack::as_u64_le( & message.data[24..32]), unpack::as_u64_le( & message.data[32..40])],
-    })
+    Ok(FlagBits { incompat_flags })
+}
+
+// Parses the key_len, key and value of an info message. Returns (type, name, value).
+fn parse_key_value<'a>(
+    message: &model::ULogMessage<'a>,
+) -> Result<(&'a str, &'a str, &'a [u8]), UlogParseError> {
+    let data = message.data();
+    let key_len = *data
+        .first()
+        .ok_or_else(|| UlogParseError::new(ParseErrorType::Other, "key value message was empty"))?
+        as usize;
+    if data.len() < 1 + key_len {
+        return Err(UlogParseError::new(
+            ParseErrorType::Other,
+            "key value message with too long key encountered",
+        ));
+    }
+    let key = std::str::from_utf8(&data[1..(1 + key_len)])
+        .map_err(|_| UlogParseError::new(ParseErrorType::Other, "message key is not a string"))?;
+    let parts: Vec<&str> = key.split(' ').collect();
+    if parts.len() != 2 || parts.iter().any(|e| e.is_empty()) {
+        return Err(UlogParseError::new(
+            ParseErrorType::Other,
+            &format!("invalid message key: {}", key),
+        ));
+    }
+    Ok((parts[0], parts[1], &data[(1 + key_len)..]))
 }
 
 #[derive(Debug)]
@@ -510,50 +1088,47 @@ struct Format {
     fields: Vec<Field>,
 }
 
-fn parse_format(message: &model::ULogMessage) -> Result<Format, UlogParseError> {
-    let format = std::str::from_utf8(&message.data()).map_err(|_| {
-        UlogParseError::new(ParseErrorType::Other, "format message is not a string")
-    })?;
-
-    let parts: Vec<&str> = format.split(":").collect();
-
-    if parts.len() != 2 || parts.iter().any(|e| e.is_empty()) {
-        return Err(UlogParseError::new(
-            ParseErrorType::Other,
-            &format!("invalid format string: {}", format),
-        ));
+// Returns None for invalid UTF-8, unless decoding lossily.
+fn decode_name(data: &[u8], lossy: bool) -> Option<std::borrow::Cow<'_, str>> {
+    if lossy {
+        Some(String::from_utf8_lossy(data))
+    } else {
+        std::str::from_utf8(data)
+            .ok()
+            .map(std::borrow::Cow::Borrowed)
     }
+}
 
-    let mut result = Format::default();
-    result.message_name = parts[0].to_string();
-
-    for type_and_name in parts[1].split(";").filter(|s| !s.is_empty()) {
-        let split: Vec<&str> = type_and_name.split(" ").collect();
-
-        if split.len() != 2 || split.iter().any(|e| e.is_empty()) {
-            return Err(UlogParseError::new(
-                ParseErrorType::Other,
-                &format!("invalid type_and_name string: {}", type_and_name),
-            ));
-        }
-        let field_type = MaybeRepeatedType::from_str(split[0])?;
-        let field_name = split[1].to_string();
-        result.fields.push(Field {
-            field_type,
-            field_name,
-        })
-    }
+fn parse_format(message: &model::ULogMessage, lossy: bool) -> Result<Format, UlogParseError> {
+    let format = decode_name(message.data(), lossy).ok_or_else(|| {
+        UlogParseError::new(ParseErrorType::Other, "format message is not a string")
+    })?;
 
-    let unique_field_names =
-        HashSet::<String>::from_iter(result.fields.iter().map(|f| f.field_name.to_string()));
-    if unique_field_names.len() != result.fields.len() {
-        return Err(UlogParseError::new(
-            ParseErrorType::Other,
-            &format!("duplicate field name in format string: {}", format),
-        ));
-    }
+    let parsed = px4_ulog_core::parse_format(&format).map_err(|e| {
+        let description = match e {
+            FormatError::InvalidFormat => format!("invalid format string: {}", format),
+            FormatError::InvalidField(field) => format!("invalid type_and_name string: {}", field),
+            FormatError::InvalidType(written_type) => {
+                format!("invalid type string: {}", written_type)
+            }
+            FormatError::DuplicateField(_) => {
+                format!("duplicate field name in format string: {}", format)
+            }
+        };
+        UlogParseError::new(ParseErrorType::Other, &description)
+    })?;
 
-    Ok(result)
+    Ok(Format {
+        message_name: parsed.message_name.to_string(),
+        fields: parsed
+            .fields
+            .iter()
+            .map(|field| Field {
+                field_name: field.field_name.to_string(),
+                field_type: MaybeRepeatedType::from_field(field),
+            })
+            .collect(),
+    })
 }
 
 fn flatten_data_type(
@@ -745,13 +1320,12 @@ fn add_flattened_message(
                 // padding is skipped on the last field on the base level
                 break;
             }
-            let append_to;
             // Only add the name for non-padding fields
-            if field.field_name.starts_with("_padding") {
-                append_to = &mut padding_trash_vec;
+            let append_to = if field.field_name.starts_with("_padding") {
+                &mut padding_trash_vec
             } else {
-                append_to = list_to_append_to;
-            }
+                &mut *list_to_append_to
+            };
             offset = flatten_field(
                 field,
                 offset,
@@ -773,6 +1347,43 @@ fn add_flattened_message(
     }
 }
 
+// The size of a definition including nested messages, without the msg_id and like
+// add_flattened_message without the padding at the end of the top level. Does not overflow like
+// flattening, but returns None for unknown or circular references, which flattening reports.
+fn definition_size(
+    message_name: &str,
+    message_formats: &HashMap<String, Vec<Field>>,
+    nested: bool,
+    visiting: &mut HashSet<String>,
+) -> Option<usize> {
+    let fields = message_formats.get(message_name)?;
+    if !visiting.insert(message_name.to_string()) {
+        return None;
+    }
+    let mut size = 0usize;
+    for (index, field) in fields.iter().enumerate() {
+        if !nested && index + 1 == fields.len() && field.field_name.starts_with("_padding") {
+            break;
+        }
+        let (data_type, count) = match &field.field_type {
+            MaybeRepeatedType::Singular(data_type) => (data_type, 1),
+            MaybeRepeatedType::Repeated(data_type, n) => (data_type, (*n).max(0) as usize),
+        };
+        let type_size = match data_type {
+            DataType::Int8 | DataType::UInt8 | DataType::Bool | DataType::Char => 1,
+            DataType::Int16 | DataType::UInt16 => 2,
+            DataType::Int32 | DataType::UInt32 | DataType::Float => 4,
+            DataType::Int64 | DataType::UInt64 | DataType::Double => 8,
+            DataType::Message(nested_name) => {
+                definition_size(nested_name, message_formats, true, visiting)?
+            }
+        };
+        size = size.saturating_add(type_size.saturating_mul(count));
+    }
+    visiting.remove(message_name);
+    Some(size)
+}
+
 fn flatten_format(
     message_formats: &HashMap<String, Vec<Field>>,
 ) -> Result<HashMap<String, FlattenedFormat>, UlogParseError> {
@@ -802,10 +1413,25 @@ fn flatten_format(
                 &format!("Message is too big {}", message_name),
             ));
         }
-        result.insert(
-            message_name.to_string(),
-            FlattenedFormat::new(message_name.to_string(), flattened_fields, u16_offset)?,
-        );
+        let mut flattened_format =
+            FlattenedFormat::new(message_name.to_string(), flattened_fields, u16_offset)?;
+        // The padding skipped at the end, see add_flattened_message
+        if let Some(last_field) = field
+            .1
+            .last()
+            .filter(|f| f.field_name.starts_with("_padding"))
+        {
+            let padding_end = flatten_field(
+                last_field,
+                offset,
+                message_formats,
+                "".to_string(),
+                &mut already_added_messages,
+                &mut Vec::new(),
+            )?;
+            flattened_format.trailing_padding = (padding_end - offset) as u16;
+        }
+        result.insert(message_name.to_string(), flattened_format);
     }
 
     Ok(result)
@@ -826,25 +1452,41 @@ pub fn read_file_with_simple_callback<CB: FnMut(&Message) -> SimpleCallbackResul
     file_path: &str,
     c: &mut CB,
 ) -> Result<usize, std::io::Error> {
+    read_file_with_simple_callback_chunked(file_path, 960 * 1024, c)
+}
+
+/// Like `read_file_with_simple_callback`, but reads the file in chunks of `chunk_size` bytes,
+/// e.g. to bound the memory use. Messages may span several chunks.
+pub fn read_file_with_simple_callback_chunked<CB: FnMut(&Message) -> SimpleCallbackResult>(
+    file_path: &str,
+    chunk_size: usize,
+    c: &mut CB,
+) -> Result<usize, std::io::Error> {
+    if chunk_size == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "chunk size must not be 0",
+        ));
+    }
     let stop_reading = Cell::new(false);
     let c_cell: RefCell<&mut CB> = RefCell::new(c);
     let mut wrapped_data_message_callback = |data_message: &DataMessage| {
         if let SimpleCallbackResult::Stop =
-            c_cell.borrow_mut().deref_mut()(&Message::Data(&data_message))
+            c_cell.borrow_mut().deref_mut()(&Message::Data(data_message))
         {
             stop_reading.set(true);
         }
     };
     let mut wrapped_string_message_callback = |data_message: &model::LoggedStringMessage| {
         if let SimpleCallbackResult::Stop =
-            c_cell.borrow_mut().deref_mut()(&Message::LoggedMessage(&data_message))
+            c_cell.borrow_mut().deref_mut()(&Message::LoggedMessage(data_message))
         {
             stop_reading.set(true);
         }
     };
     let mut wrapped_parameter_message_callback = |parameter_message: &model::ParameterMessage| {
         if let SimpleCallbackResult::Stop =
-            c_cell.borrow_mut().deref_mut()(&Message::ParameterMessage(&parameter_message))
+            c_cell.borrow_mut().deref_mut()(&Message::ParameterMessage(parameter_message))
         {
             stop_reading.set(true);
         }
@@ -856,17 +1498,662 @@ pub fn read_file_with_simple_callback<CB: FnMut(&Message) -> SimpleCallbackResul
 
     let mut total_bytes_read: usize = 0;
     let mut f = std::fs::File::open(file_path)?;
-    const READ_START: usize = 64 * 1024;
-    let mut buf = [0u8; 1024 * 1024];
+    let mut buf = vec![0u8; chunk_size];
     while !stop_reading.get() {
-        let num_bytes_read = f.read(&mut buf[READ_START..])?;
+        let num_bytes_read = f.read(&mut buf)?;
         if num_bytes_read == 0 {
             break;
         }
         log_parser
-            .consume_bytes(&buf[READ_START..(READ_START + num_bytes_read)])
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("err: {:?}", e)))?;
+            .consume_bytes(&buf[..num_bytes_read])
+            .map_err(|e| std::io::Error::other(format!("err: {:?}", e)))?;
         total_bytes_read += num_bytes_read;
     }
     Ok(total_bytes_read)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_parser::test_util::TestLog;
+
+    #[test]
+    fn reads_file_with_small_chunks() {
+        let filename = format!("{}/tests/fixtures/sample.ulg", env!("CARGO_MANIFEST_DIR"));
+        let count_messages = |chunk_size: Option<usize>| {
+            let mut counts = [0usize; 3];
+            let mut timestamp_sum = 0u64;
+            let mut callback = |msg: &Message| {
+                match msg {
+                    Message::Data(data) => {
+                        counts[0] += 1;
+                        timestamp_sum = timestamp_sum.wrapping_add(data.timestamp().unwrap_or(0));
+                    }
+                    Message::LoggedMessage(_) => counts[1] += 1,
+                    Message::ParameterMessage(_) => counts[2] += 1,
+                }
+                SimpleCallbackResult::KeepReading
+            };
+            let bytes_read = match chunk_size {
+                Some(chunk_size) => {
+                    read_file_with_simple_callback_chunked(&filename, chunk_size, &mut callback)
+                }
+                None => read_file_with_simple_callback(&filename, &mut callback),
+            }
+            .unwrap();
+            (bytes_read, counts, timestamp_sum)
+        };
+        let expected = count_messages(None);
+        assert!(expected.1[0] > 0);
+        assert_eq!(4, expected.1[1]);
+        assert_eq!(expected, count_messages(Some(512)));
+        let mut keep_reading = |_: &Message| SimpleCallbackResult::KeepReading;
+        assert!(read_file_with_simple_callback_chunked(&filename, 0, &mut keep_reading).is_err());
+    }
+
+    fn parse_data_format(file_name: &str) -> DataFormat {
+        let filename = format!(
+            "{}/tests/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            file_name
+        );
+        let bytes = std::fs::read(filename).unwrap();
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&bytes).unwrap();
+        parser.get_final_data_format()
+    }
+
+    #[test]
+    fn calls_raw_message_callback_for_every_message() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let bytes = std::fs::read(filename).unwrap();
+        let mut counts = HashMap::<model::MessageType, usize>::new();
+        let mut data_bytes = 0;
+        let mut callback = |msg_type: model::MessageType, data: &[u8]| {
+            *counts.entry(msg_type).or_default() += 1;
+            if msg_type == model::MessageType::Data {
+                data_bytes += data.len();
+            }
+        };
+        let mut data_messages = 0;
+        let mut data_callback = |_: &DataMessage| data_messages += 1;
+        let mut parser = LogParser::default();
+        parser.set_raw_message_callback(&mut callback);
+        parser.set_data_message_callback(&mut data_callback);
+        parser.consume_bytes(&bytes).unwrap();
+        parser.get_final_data_format();
+
+        assert_eq!(Some(&1), counts.get(&model::MessageType::FlagBits));
+        assert_eq!(Some(&544), counts.get(&model::MessageType::Parameter));
+        assert_eq!(Some(&2), counts.get(&model::MessageType::Logging));
+        assert!(counts[&model::MessageType::Format] > 0);
+        assert!(counts[&model::MessageType::Data] >= data_messages);
+        assert!(data_bytes > 0);
+    }
+
+    #[test]
+    fn validates_sync_messages() {
+        let log = |sync: &[u8]| {
+            TestLog::new()
+                .format("test_message:uint64_t timestamp")
+                .add_logged_message(0, 0, "test_message")
+                .data(0, &1u64.to_le_bytes())
+                .message(b'S', sync)
+                .data(0, &2u64.to_le_bytes())
+                .bytes()
+        };
+
+        let mut parser = LogParser::default();
+        assert!(parser.consume_bytes(&log(&SYNC_MAGIC)).is_ok());
+
+        let mut corrupted_magic = SYNC_MAGIC;
+        corrupted_magic[3] = 0;
+        let mut parser = LogParser::default();
+        assert!(parser.consume_bytes(&log(&corrupted_magic)).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let log = |version: u8| {
+            TestLog::with_header(version, 0)
+                .format("test_message:uint64_t timestamp")
+                .add_logged_message(0, 0, "test_message")
+                .data(0, &1u64.to_le_bytes())
+                .bytes()
+        };
+        for version in &[0, 1] {
+            let mut parser = LogParser::default();
+            parser.consume_bytes(&log(*version)).unwrap();
+            assert_eq!(*version, parser.version());
+        }
+        let error = LogParser::default().consume_bytes(&log(2)).err().unwrap();
+        match error.error_type() {
+            ParseErrorType::UnsupportedVersion => {}
+            other => panic!("unexpected error type {:?}", other),
+        }
+        assert_eq!(
+            "unsupported ULog version 2, supported versions are [0, 1]",
+            error.description()
+        );
+    }
+
+    #[test]
+    fn scans_for_header_after_junk() {
+        let mut bytes = b"\xef\xbb\xbf\n".to_vec();
+        bytes.extend_from_slice(
+            &TestLog::new()
+                .format("test_message:uint64_t timestamp")
+                .add_logged_message(0, 0, "test_message")
+                .data(0, &1u64.to_le_bytes())
+                .bytes(),
+        );
+        assert!(LogParser::default().consume_bytes(&bytes).is_err());
+
+        for chunk_size in &[1, 5, bytes.len()] {
+            let mut count = 0;
+            let mut callback = |_: &model::DataMessage| count += 1;
+            let mut parser = LogParser::default();
+            parser.set_scan_for_header(true);
+            parser.set_data_message_callback(&mut callback);
+            for chunk in bytes.chunks(*chunk_size) {
+                parser.consume_bytes(chunk).unwrap();
+            }
+            assert_eq!(4, parser.skipped_header_bytes());
+            drop(parser);
+            assert_eq!(1, count);
+        }
+
+        let mut parser = LogParser::default();
+        parser.set_scan_for_header(true);
+        assert!(parser.consume_bytes(&[0; 2 * HEADER_SCAN_LIMIT]).is_err());
+    }
+
+    #[test]
+    fn skips_unregistered_data_in_tolerant_mode() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .add_logged_message(0, 0, "test_message")
+            .data(0, &1u64.to_le_bytes())
+            .data(7, &2u64.to_le_bytes())
+            .data(0, &3u64.to_le_bytes())
+            .bytes();
+        assert!(LogParser::default().consume_bytes(&log).is_err());
+
+        let mut timestamps = Vec::new();
+        let mut callback = |msg: &model::DataMessage| timestamps.push(msg.timestamp().unwrap());
+        let mut parser = LogParser::default();
+        parser.set_skip_unregistered_data(true);
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        assert_eq!(1, parser.skipped_data_messages());
+        drop(parser);
+        assert_eq!(vec![1, 3], timestamps);
+    }
+
+    #[test]
+    fn accepts_trailing_padding_of_appended_data() {
+        let sample = |timestamp: u64, padding: usize| {
+            let mut payload = timestamp.to_le_bytes().to_vec();
+            payload.push(42);
+            payload.resize(payload.len() + padding, 0);
+            payload
+        };
+        // The main data leaves out the padding, the appended data keeps it
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp;uint8_t x;uint8_t[7] _padding0")
+            .add_logged_message(0, 0, "test_message")
+            .data(0, &sample(1, 0))
+            .data(0, &sample(2, 7))
+            .bytes();
+        assert!(LogParser::default().consume_bytes(&log).is_err());
+
+        let mut rows = Vec::new();
+        let mut callback = |msg: &model::DataMessage| {
+            let x = msg.flattened_format.get_field_parser::<u8>("x").unwrap();
+            rows.push((msg.timestamp().unwrap(), x.parse(msg.data)));
+        };
+        let mut parser = LogParser::default();
+        parser.set_accept_trailing_padding(true);
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        let data_format = parser.get_final_data_format();
+        assert_eq!(
+            7,
+            data_format.flattened_formats()["test_message"].trailing_padding()
+        );
+        assert_eq!(vec![(1, 42), (2, 42)], rows);
+    }
+
+    #[test]
+    fn skips_malformed_metadata_messages() {
+        let log = TestLog::new()
+            .message(b'I', b"\x0bno_type_key")
+            .format("test_message:uint64_t timestamp")
+            .add_logged_message(0, 0, "test_message")
+            .message(b'O', &[1])
+            .message(b'M', &[])
+            .multiple_info(false, "no_type_key", b"")
+            .data(0, &1u64.to_le_bytes())
+            .bytes();
+        let mut num_messages = 0;
+        let mut data_callback = |_: &model::DataMessage| num_messages += 1;
+        let mut errors = Vec::new();
+        let mut error_callback =
+            |msg_type: model::MessageType, _: &UlogParseError| errors.push(msg_type);
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut data_callback);
+        parser.set_message_error_callback(&mut error_callback);
+        parser.consume_bytes(&log).unwrap();
+        drop(parser);
+        assert_eq!(1, num_messages);
+        assert_eq!(
+            vec![
+                model::MessageType::Info,
+                model::MessageType::Dropout,
+                model::MessageType::MultipleInfo,
+                model::MessageType::MultipleInfo
+            ],
+            errors
+        );
+        assert!(LogParser::default().consume_bytes(&log).is_ok());
+    }
+
+    #[test]
+    fn rejects_truncated_parameter_messages() {
+        let default_without_parameter = TestLog::new().message(b'Q', &[1]).bytes();
+        assert!(LogParser::default()
+            .consume_bytes(&default_without_parameter)
+            .is_err());
+        let key_longer_than_message = TestLog::new().message(b'P', &[200, b'x']).bytes();
+        assert!(LogParser::default()
+            .consume_bytes(&key_longer_than_message)
+            .is_err());
+    }
+
+    #[test]
+    fn passes_on_parameters_of_unusual_types_leniently() {
+        let parameter = |type_and_name: &str, value: &[u8]| {
+            let mut payload = vec![type_and_name.len() as u8];
+            payload.extend_from_slice(type_and_name.as_bytes());
+            payload.extend_from_slice(value);
+            payload
+        };
+        let log = TestLog::new()
+            .message(b'P', &parameter("double PARAM_D", &2.5f64.to_le_bytes()))
+            .message(b'P', &parameter("int32_t PARAM_I", &7i32.to_le_bytes()))
+            .bytes();
+        assert!(LogParser::default().consume_bytes(&log).is_err());
+
+        let mut raw_parameters = Vec::new();
+        let mut int_values = Vec::new();
+        let mut callback = |msg: &ParameterMessage| match msg {
+            ParameterMessage::Raw(name, type_name, bytes, _) => {
+                raw_parameters.push((name.to_string(), type_name.to_string(), bytes.to_vec()))
+            }
+            ParameterMessage::Int32(_, value, _) => int_values.push(*value),
+            ParameterMessage::Float(..) => panic!("unexpected float parameter"),
+        };
+        let mut parser = LogParser::default();
+        parser.set_lenient_parameter_types(true);
+        parser.set_parameter_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        drop(parser);
+        assert_eq!(vec![7], int_values);
+        assert_eq!(1, raw_parameters.len());
+        let (name, type_name, bytes) = &raw_parameters[0];
+        assert_eq!(("PARAM_D", "double"), (name.as_str(), type_name.as_str()));
+        assert_eq!(&2.5f64.to_le_bytes(), &bytes[..]);
+    }
+
+    #[test]
+    fn parses_invalid_utf8_names_leniently() {
+        let log = TestLog::new()
+            .message(b'F', b"test_\xffmessage:uint64_t timestamp;uint8_t x")
+            .message(b'A', b"\x00\x00\x00test_\xffmessage")
+            .data(0, &[1, 0, 0, 0, 0, 0, 0, 0, 42])
+            .bytes();
+        assert!(LogParser::default().consume_bytes(&log).is_err());
+
+        let mut rows = Vec::new();
+        let mut callback = |msg: &model::DataMessage| {
+            let x = msg.flattened_format.get_field_parser::<u8>("x").unwrap();
+            rows.push((msg.flattened_format.message_name.clone(), x.parse(msg.data)));
+        };
+        let mut parser = LogParser::default();
+        parser.set_lossy_names(true);
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        assert_eq!(vec![("test_\u{fffd}message".to_string(), 42)], rows);
+    }
+
+    #[test]
+    fn checks_incompat_flags_in_strict_mode() {
+        let log = |incompat_flags0: u8| {
+            let mut flag_bits = [0u8; 40];
+            flag_bits[8] = incompat_flags0;
+            TestLog::with_header(1, 0)
+                .message(b'B', &flag_bits)
+                .format("test_message:uint64_t timestamp")
+                .add_logged_message(0, 0, "test_message")
+                .data(0, &1u64.to_le_bytes())
+                .bytes()
+        };
+        let parse = |log: &[u8], strict: bool| {
+            let mut num_messages = 0;
+            let mut callback = |_: &DataMessage| num_messages += 1;
+            let mut parser = LogParser::default();
+            parser.set_strict_incompat_flags(strict);
+            parser.set_data_message_callback(&mut callback);
+            let result = parser.consume_bytes(log);
+            drop(parser);
+            result.map(|_| num_messages)
+        };
+
+        // Bit 0 marks appended data, which is known
+        assert_eq!(1, parse(&log(1), true).unwrap());
+        assert_eq!(1, parse(&log(2), false).unwrap());
+        match parse(&log(2), true) {
+            Err(e) => match e.error_type() {
+                ParseErrorType::IncompatibleFlags => {}
+                other => panic!("unexpected error type {:?}", other),
+            },
+            Ok(_) => panic!("unknown incompat flag accepted"),
+        }
+    }
+
+    #[test]
+    fn reassembles_event_arguments() {
+        let mut payload = 1234u64.to_le_bytes().to_vec();
+        payload.extend_from_slice(&7u32.to_le_bytes());
+        payload.extend_from_slice(&1u16.to_le_bytes());
+        let arguments: Vec<u8> = (0..40).map(|i| i * 3).collect();
+        payload.extend_from_slice(&arguments);
+        payload.push(0x41);
+        let log = TestLog::new()
+            .format(
+                "event:uint64_t timestamp;uint32_t id;uint16_t event_sequence;\
+                 uint8_t[40] arguments;uint8_t log_levels;uint8_t[5] _padding0;",
+            )
+            .add_logged_message(0, 0, "event")
+            .data(0, &payload)
+            .bytes();
+        let mut decoded = Vec::new();
+        let mut callback = |msg: &DataMessage| {
+            decoded.push((msg.get_bytes("arguments"), msg.get_bytes("id")));
+        };
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        drop(parser);
+        assert_eq!(vec![(Some(arguments), None)], decoded);
+    }
+
+    #[test]
+    fn parses_headerless_data_with_known_format() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp;float x")
+            .add_logged_message(0, 3, "test_message")
+            .data(
+                3,
+                &[&1u64.to_le_bytes()[..], &1.5f32.to_le_bytes()].concat(),
+            )
+            .bytes();
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&log).unwrap();
+        let data_format = parser.get_final_data_format();
+
+        let data_only = TestLog::headerless()
+            .data(
+                3,
+                &[&2u64.to_le_bytes()[..], &2.5f32.to_le_bytes()].concat(),
+            )
+            .data(
+                3,
+                &[&3u64.to_le_bytes()[..], &3.5f32.to_le_bytes()].concat(),
+            )
+            .bytes();
+        let mut values = Vec::new();
+        let mut callback = |msg: &DataMessage| {
+            let parser = msg.flattened_format.get_field_parser::<f32>("x").unwrap();
+            values.push(parser.parse(msg.data));
+        };
+        let mut parser = LogParser::with_data_format(data_format);
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&data_only).unwrap();
+        drop(parser);
+        assert_eq!(vec![2.5, 3.5], values);
+    }
+
+    #[test]
+    fn describes_size_mismatch() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp;float x;float y")
+            .add_logged_message(0, 0, "test_message")
+            .data(
+                0,
+                &[&1u64.to_le_bytes()[..], &1.5f32.to_le_bytes()].concat(),
+            )
+            .bytes();
+        let error = LogParser::default().consume_bytes(&log).err().unwrap();
+        assert_eq!(
+            "data message of test_message has 14 bytes but the format needs 18, \
+             fields beyond the data: y; \
+             layout: timestamp UInt64 at 2..10, x Float at 10..14, y Float at 14..18",
+            error.description()
+        );
+
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .add_logged_message(0, 0, "test_message")
+            .data(0, &[&1u64.to_le_bytes()[..], &[0; 3]].concat())
+            .bytes();
+        let error = LogParser::default().consume_bytes(&log).err().unwrap();
+        assert!(error
+            .description()
+            .contains("3 bytes not covered by any field"));
+    }
+
+    #[test]
+    fn finish_detects_truncation() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .add_logged_message(0, 0, "test_message")
+            .data(0, &1u64.to_le_bytes())
+            .bytes();
+
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&log).unwrap();
+        let data_format = parser.finish().unwrap();
+        assert!(data_format.flattened_format.contains_key("test_message"));
+
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&log[..log.len() - 3]).unwrap();
+        match parser.finish() {
+            Err(e) => match e.error_type() {
+                ParseErrorType::Truncated => {}
+                other => panic!("unexpected error type {:?}", other),
+            },
+            Ok(_) => panic!("truncation not detected"),
+        }
+
+        // Only definitions, without any data section
+        let definitions_only = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .bytes();
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&definitions_only).unwrap();
+        let data_format = parser.finish().unwrap();
+        assert!(data_format.flattened_format.contains_key("test_message"));
+    }
+
+    #[test]
+    fn handles_message_without_fields() {
+        let log = TestLog::new()
+            .format("empty_message:")
+            .add_logged_message(0, 0, "empty_message")
+            .data(0, &[])
+            .bytes();
+        let mut rows = Vec::new();
+        let mut callback = |msg: &DataMessage| {
+            rows.push((msg.data.len(), msg.flattened_format.fields.len()));
+        };
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        assert_eq!(
+            2,
+            parser.get_final_data_format().flattened_format["empty_message"].size()
+        );
+        assert_eq!(vec![(2, 0)], rows);
+    }
+
+    #[test]
+    fn finds_topics_with_field() {
+        let data_format = parse_data_format("6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg");
+        let topics = data_format.topics_with_field("timestamp");
+        assert!(topics.len() > 30);
+        assert!(topics.contains(&"vehicle_gps_position"));
+        assert_eq!(
+            vec!["vehicle_gps_position"],
+            data_format.topics_with_field("satellites_used")
+        );
+        assert!(data_format.topics_with_field("satellites").is_empty());
+    }
+
+    #[test]
+    fn lists_flattened_formats() {
+        let data_format = parse_data_format("6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg");
+        let formats = data_format.flattened_formats();
+        assert_eq!(
+            data_format.topics_with_field("timestamp").len(),
+            formats.len()
+        );
+        let battery_status = &formats["battery_status"];
+        assert_eq!("battery_status", battery_status.message_name());
+        assert_eq!(21, battery_status.fields.len());
+    }
+
+    #[test]
+    fn registers_instances_by_msg_id() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .add_logged_message(1, 3, "test_message")
+            .data(3, &1u64.to_le_bytes())
+            .bytes();
+        let mut msg_ids = Vec::new();
+        let mut callback =
+            |msg: &model::DataMessage| msg_ids.push((msg.msg_id, msg.multi_id.clone()));
+        let mut parser = LogParser::default();
+        parser.set_data_message_callback(&mut callback);
+        parser.consume_bytes(&log).unwrap();
+        let mut data_format = parser.get_final_data_format();
+        let (format, multi_id, _) = data_format.get_message_description(MsgId::new(3)).unwrap();
+        assert_eq!("test_message", format.message_name);
+        assert_eq!(MultiId::new(1), *multi_id);
+        assert!(data_format.get_message_description(MsgId::new(1)).is_none());
+        assert_eq!(vec![(MsgId::new(3), MultiId::new(1))], msg_ids);
+
+        let mut data_format = DataFormat::new(data_format.flattened_formats().clone());
+        data_format
+            .register_msg_id(MsgId::new(7), "test_message", 0)
+            .unwrap();
+        assert!(data_format
+            .register_msg_id(MsgId::new(7), "test_message", 1)
+            .is_err());
+    }
+
+    #[test]
+    fn validates_sizes_of_definitions() {
+        assert_eq!(
+            Ok(()),
+            parse_data_format("esc_status_log.ulg").validate_sizes()
+        );
+
+        // 2 * 4096 * 8 bytes of nested values, the msg_id does not fit anymore
+        let log = TestLog::new()
+            .format("inner:uint64_t[4096] values")
+            .format("outer:uint64_t timestamp;inner[2] inners")
+            .format("small:uint64_t timestamp;inner inner")
+            .bytes();
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&log).unwrap();
+        let data_format = parser.get_final_data_format();
+        assert_eq!(Err(vec!["outer".to_string()]), data_format.validate_sizes());
+
+        let mut parser = LogParser::default();
+        parser.consume_bytes(&log).unwrap();
+        assert!(parser.finish().is_err());
+    }
+
+    #[test]
+    fn reports_consistent_sizes() {
+        let data_format = parse_data_format("6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg");
+        let diagnostics = data_format.size_diagnostics();
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for (msg_id, declared, observed) in diagnostics {
+            assert_eq!(declared, observed, "msg_id {}", msg_id.value());
+        }
+    }
+
+    #[test]
+    fn shares_schemas_through_cache() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let bytes = std::fs::read(&filename).unwrap();
+        let cache = SchemaCache::new();
+        let mut hashes = Vec::new();
+        for _ in 0..2 {
+            let mut parser = LogParser::default();
+            parser.set_schema_cache(&cache);
+            parser.consume_bytes(&bytes).unwrap();
+            hashes.push(parser.get_final_data_format().schema_hash());
+        }
+        assert_eq!(1, cache.len());
+        assert_eq!(hashes[0], hashes[1]);
+        assert_eq!(
+            parse_data_format("esc_status_log.ulg").schema_hash(),
+            hashes[0]
+        );
+    }
+
+    #[test]
+    fn keeps_cache_key_when_flattening_again() {
+        let log = TestLog::new()
+            .format("test_message:uint64_t timestamp")
+            .bytes();
+        let cache = SchemaCache::new();
+        let mut parser = LogParser::default();
+        parser.set_schema_cache(&cache);
+        parser.consume_bytes(&log).unwrap();
+        parser.flatten_definitions().unwrap();
+        parser.flatten_definitions().unwrap();
+        assert_eq!(1, cache.len());
+        assert!(cache
+            .schemas
+            .lock()
+            .unwrap()
+            .keys()
+            .all(|key| !key.is_empty()));
+    }
+
+    #[test]
+    fn schema_hash_is_stable() {
+        let log = "6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg";
+        let a = parse_data_format(log);
+        let mut b = parse_data_format(log);
+        assert_eq!(a.schema_hash(), b.schema_hash());
+        assert_ne!(
+            a.schema_hash(),
+            parse_data_format("esc_status_log.ulg").schema_hash()
+        );
+
+        b.flattened_format.remove("ping");
+        assert_ne!(a.schema_hash(), b.schema_hash());
+    }
+}
@@ -1,12 +1,23 @@
 pub mod file_reader;
 pub mod model;
 mod model_helper;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 pub use self::file_reader::read_file_with_simple_callback;
+pub use self::file_reader::read_file_with_simple_callback_chunked;
 pub use self::file_reader::LogParser;
 pub use self::file_reader::Message;
+pub use self::file_reader::SchemaCache;
 pub use self::model::DataMessage;
+pub use self::model::DefaultParameterMessage;
+pub use self::model::DefaultType;
+pub use self::model::DropoutMessage;
+pub use self::model::InfoMessage;
 pub use self::model::LogStage;
+pub use self::model::MsgId;
+pub use self::model::MultipleInfoMessage;
+pub use self::model::OwnedDataMessage;
 pub use self::model::ParameterMessage;
 pub use self::model::FieldParser;
 pub use self::model::LoggedStringMessage;
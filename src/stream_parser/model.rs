@@ -1,8 +1,9 @@
 use super::model_helper::{FlattenedFieldTypeMatcher, LittleEndianParser};
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum MessageType {
     Unknown,
     Format,
@@ -10,6 +11,7 @@ pub enum MessageType {
     Info,
     MultipleInfo,
     Parameter,
+    DefaultParameter,
     AddLoggedMessage,
     RemoveLoggedMessage,
     Sync,
@@ -18,6 +20,27 @@ pub enum MessageType {
     FlagBits,
 }
 
+impl MessageType {
+    /// The type byte of the message as written in the log, None for unknown types
+    pub fn type_char(&self) -> Option<char> {
+        match self {
+            MessageType::Format => Some('F'),
+            MessageType::Data => Some('D'),
+            MessageType::Info => Some('I'),
+            MessageType::MultipleInfo => Some('M'),
+            MessageType::Parameter => Some('P'),
+            MessageType::DefaultParameter => Some('Q'),
+            MessageType::AddLoggedMessage => Some('A'),
+            MessageType::RemoveLoggedMessage => Some('R'),
+            MessageType::Sync => Some('S'),
+            MessageType::Dropout => Some('O'),
+            MessageType::Logging => Some('L'),
+            MessageType::FlagBits => Some('B'),
+            MessageType::Unknown => None,
+        }
+    }
+}
+
 pub struct ULogMessage<'a> {
     msg_type: u8,
     pub data: &'a [u8],
@@ -28,7 +51,7 @@ impl<'a> ULogMessage<'a> {
     //pub fn parse(data: &'a [u8]) -> (Option<Self>, usize) {}
 
     pub fn new(msg_type: u8, data: &'a [u8]) -> Self {
-        if data.len() > u16::max_value() as usize {
+        if data.len() > u16::MAX as usize {
             panic!("slice is too long");
         }
         Self { msg_type, data }
@@ -41,6 +64,7 @@ impl<'a> ULogMessage<'a> {
             'I' => MessageType::Info,
             'M' => MessageType::MultipleInfo,
             'P' => MessageType::Parameter,
+            'Q' => MessageType::DefaultParameter,
             'A' => MessageType::AddLoggedMessage,
             'R' => MessageType::RemoveLoggedMessage,
             'S' => MessageType::Sync,
@@ -60,7 +84,7 @@ impl<'a> ULogMessage<'a> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum FlattenedFieldType {
     Int8,
     UInt8,
@@ -76,6 +100,78 @@ pub enum FlattenedFieldType {
     Char,
 }
 
+impl FlattenedFieldType {
+    /// Number of bytes a single value of this type occupies in a data message
+    pub fn size(&self) -> u16 {
+        match self {
+            FlattenedFieldType::Int8
+            | FlattenedFieldType::UInt8
+            | FlattenedFieldType::Bool
+            | FlattenedFieldType::Char => 1,
+            FlattenedFieldType::Int16 | FlattenedFieldType::UInt16 => 2,
+            FlattenedFieldType::Int32 | FlattenedFieldType::UInt32 | FlattenedFieldType::Float => 4,
+            FlattenedFieldType::Int64 | FlattenedFieldType::UInt64 | FlattenedFieldType::Double => {
+                8
+            }
+        }
+    }
+
+    // Decodes a value of this type from the start of bytes, which must be long enough.
+    pub(crate) fn decode_value(&self, bytes: &[u8]) -> FlattenedFieldValue {
+        match self {
+            FlattenedFieldType::Int8 => FlattenedFieldValue::Int8(i8::parse(bytes)),
+            FlattenedFieldType::UInt8 => FlattenedFieldValue::UInt8(u8::parse(bytes)),
+            FlattenedFieldType::Int16 => FlattenedFieldValue::Int16(i16::parse(bytes)),
+            FlattenedFieldType::UInt16 => FlattenedFieldValue::UInt16(u16::parse(bytes)),
+            FlattenedFieldType::Int32 => FlattenedFieldValue::Int32(i32::parse(bytes)),
+            FlattenedFieldType::UInt32 => FlattenedFieldValue::UInt32(u32::parse(bytes)),
+            FlattenedFieldType::Int64 => FlattenedFieldValue::Int64(i64::parse(bytes)),
+            FlattenedFieldType::UInt64 => FlattenedFieldValue::UInt64(u64::parse(bytes)),
+            FlattenedFieldType::Float => FlattenedFieldValue::Float(f32::parse(bytes)),
+            FlattenedFieldType::Double => FlattenedFieldValue::Double(f64::parse(bytes)),
+            FlattenedFieldType::Bool => FlattenedFieldValue::Bool(bool::parse(bytes)),
+            FlattenedFieldType::Char => FlattenedFieldValue::Char(char::parse(bytes)),
+        }
+    }
+
+    /// The inverse of `type_name`
+    pub fn from_type_name(type_name: &str) -> Option<Self> {
+        match type_name {
+            "int8_t" => Some(FlattenedFieldType::Int8),
+            "uint8_t" => Some(FlattenedFieldType::UInt8),
+            "int16_t" => Some(FlattenedFieldType::Int16),
+            "uint16_t" => Some(FlattenedFieldType::UInt16),
+            "int32_t" => Some(FlattenedFieldType::Int32),
+            "uint32_t" => Some(FlattenedFieldType::UInt32),
+            "int64_t" => Some(FlattenedFieldType::Int64),
+            "uint64_t" => Some(FlattenedFieldType::UInt64),
+            "float" => Some(FlattenedFieldType::Float),
+            "double" => Some(FlattenedFieldType::Double),
+            "bool" => Some(FlattenedFieldType::Bool),
+            "char" => Some(FlattenedFieldType::Char),
+            _ => None,
+        }
+    }
+
+    /// The type name as written in ULog format definitions, e.g. `uint8_t`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FlattenedFieldType::Int8 => "int8_t",
+            FlattenedFieldType::UInt8 => "uint8_t",
+            FlattenedFieldType::Int16 => "int16_t",
+            FlattenedFieldType::UInt16 => "uint16_t",
+            FlattenedFieldType::Int32 => "int32_t",
+            FlattenedFieldType::UInt32 => "uint32_t",
+            FlattenedFieldType::Int64 => "int64_t",
+            FlattenedFieldType::UInt64 => "uint64_t",
+            FlattenedFieldType::Float => "float",
+            FlattenedFieldType::Double => "double",
+            FlattenedFieldType::Bool => "bool",
+            FlattenedFieldType::Char => "char",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum FlattenedFieldValue {
     Int8(i8),
@@ -92,7 +188,39 @@ pub enum FlattenedFieldValue {
     Char(char),
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+impl FlattenedFieldValue {
+    /// The numeric code of a `char`, e.g. for char fields used as enums, or a `uint8_t` value.
+    /// Chars are decoded from a single byte, so this is lossless.
+    pub fn as_u8(&self) -> Option<u8> {
+        match self {
+            FlattenedFieldValue::Char(value) => Some(*value as u8),
+            FlattenedFieldValue::UInt8(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+// Prints the inner value, floats with the precision needed to read them back.
+impl std::fmt::Display for FlattenedFieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlattenedFieldValue::Int8(value) => value.fmt(f),
+            FlattenedFieldValue::UInt8(value) => value.fmt(f),
+            FlattenedFieldValue::Int16(value) => value.fmt(f),
+            FlattenedFieldValue::UInt16(value) => value.fmt(f),
+            FlattenedFieldValue::Int32(value) => value.fmt(f),
+            FlattenedFieldValue::UInt32(value) => value.fmt(f),
+            FlattenedFieldValue::Int64(value) => value.fmt(f),
+            FlattenedFieldValue::UInt64(value) => value.fmt(f),
+            FlattenedFieldValue::Float(value) => value.fmt(f),
+            FlattenedFieldValue::Double(value) => value.fmt(f),
+            FlattenedFieldValue::Bool(value) => value.fmt(f),
+            FlattenedFieldValue::Char(value) => value.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct MultiId(u8);
 
 impl MultiId {
@@ -104,11 +232,40 @@ impl MultiId {
     }
 }
 
+/// The id by which data messages refer to a subscribed message instance, see
+/// `AddLoggedMessage`. Not to be confused with the `MultiId` of the instance.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MsgId(u16);
+
+impl MsgId {
+    pub fn new(value: u16) -> Self {
+        Self(value)
+    }
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FlattenedField {
     pub flattened_field_name: String,
     pub field_type: FlattenedFieldType,
-    pub offset: u16, // relative to the beginning of the message ()
+    pub offset: u16, // relative to the beginning of the message (), i.e. including the msg_id
+}
+
+impl FlattenedField {
+    /// The offset relative to `DataMessage::payload`, i.e. without the 2 bytes of the msg_id
+    pub fn payload_offset(&self) -> u16 {
+        self.offset.saturating_sub(2)
+    }
+
+    /// Decodes the value of this field from the data of a data message, which starts with the
+    /// msg_id like `DataMessage::data`. Returns None if data is too short.
+    pub fn decode(&self, data: &[u8]) -> Option<FlattenedFieldValue> {
+        let start = self.offset as usize;
+        let bytes = data.get(start..start + self.field_type.size() as usize)?;
+        Some(self.field_type.decode_value(bytes))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -126,13 +283,21 @@ pub struct TimestampField {
 }
 
 impl TimestampField {
-    pub fn parse_timestamp(&self, data: &[u8]) -> u64 {
-        match self.field_type {
-            TimestampFieldType::UInt8 => u8::parse(&data[self.offset as usize..]) as u64,
-            TimestampFieldType::UInt16 => u16::parse(&data[self.offset as usize..]) as u64,
-            TimestampFieldType::UInt32 => u32::parse(&data[self.offset as usize..]) as u64,
-            TimestampFieldType::UInt64 => u64::parse(&data[self.offset as usize..]),
-        }
+    /// Returns None if data is too short to contain the timestamp
+    pub fn parse_timestamp(&self, data: &[u8]) -> Option<u64> {
+        let width = match self.field_type {
+            TimestampFieldType::UInt8 => 1,
+            TimestampFieldType::UInt16 => 2,
+            TimestampFieldType::UInt32 => 4,
+            TimestampFieldType::UInt64 => 8,
+        };
+        let data = data.get(self.offset as usize..(self.offset as usize + width))?;
+        Some(match self.field_type {
+            TimestampFieldType::UInt8 => u8::parse(data) as u64,
+            TimestampFieldType::UInt16 => u16::parse(data) as u64,
+            TimestampFieldType::UInt32 => u32::parse(data) as u64,
+            TimestampFieldType::UInt64 => u64::parse(data),
+        })
     }
 }
 
@@ -155,11 +320,36 @@ impl UlogParseError {
             description: description.to_string(),
         }
     }
+
+    pub fn error_type(&self) -> &ParseErrorType {
+        &self.error_type
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl From<px4_ulog_core::FramingError> for UlogParseError {
+    fn from(e: px4_ulog_core::FramingError) -> Self {
+        match e {
+            px4_ulog_core::FramingError::InvalidHeader => UlogParseError::new(
+                ParseErrorType::InvalidFile,
+                "The header does not match the template",
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ParseErrorType {
     InvalidFile,
+    /// The header announces a ULog version which is not supported
+    UnsupportedVersion,
+    /// The log sets incompatible flag bits which are not supported
+    IncompatibleFlags,
+    /// The log ends within a message
+    Truncated,
     Other,
 }
 
@@ -168,8 +358,25 @@ pub struct FlattenedFormat {
     pub message_name: String,
     pub fields: Vec<FlattenedField>,
     name_to_field: HashMap<String, FlattenedField>,
+    // lowercase name -> flattened field name, for lookups ignoring case
+    lowercase_names: HashMap<String, String>,
     pub timestamp_field: Option<TimestampField>,
     size: u16,
+    // Bytes of the padding at the end of the definition, which is not part of size
+    pub(crate) trailing_padding: u16,
+}
+
+/// Computes the minimum message size needed to hold the given fields, including the 2 bytes of
+/// the msg_id. This is the largest offset plus the width of the field at that offset.
+///
+/// The size stored in a `FlattenedFormat` may be larger than this, since padding at the end of
+/// nested messages is not represented by any field.
+pub fn flattened_size(fields: &[FlattenedField]) -> u16 {
+    fields
+        .iter()
+        .map(|field| field.offset.saturating_add(field.field_type.size()))
+        .max()
+        .unwrap_or(2)
 }
 
 pub trait ParseableFieldType: LittleEndianParser + FlattenedFieldTypeMatcher {}
@@ -183,10 +390,26 @@ impl FlattenedFormat {
         fields: Vec<FlattenedField>,
         size: u16,
     ) -> Result<Self, UlogParseError> {
+        let required_size = flattened_size(&fields);
+        if size < required_size {
+            return Err(UlogParseError::new(
+                ParseErrorType::Other,
+                &format!(
+                    "size {} of message {} is too small for its fields, they need {} bytes",
+                    size, message_name, required_size
+                ),
+            ));
+        }
         let name_to_field: HashMap<String, FlattenedField> = fields
             .iter()
             .map(|f| (f.flattened_field_name.to_string(), (*f).clone()))
             .collect();
+        let mut lowercase_names = HashMap::new();
+        for field in &fields {
+            lowercase_names
+                .entry(field.flattened_field_name.to_lowercase())
+                .or_insert_with(|| field.flattened_field_name.to_string());
+        }
         let timestamp_field = name_to_field
             .get("timestamp")
             .and_then(|field| match field.field_type {
@@ -212,8 +435,10 @@ impl FlattenedFormat {
             message_name,
             fields,
             name_to_field,
+            lowercase_names,
             timestamp_field,
             size,
+            trailing_padding: 0,
         })
     }
 
@@ -251,7 +476,27 @@ impl FlattenedFormat {
         }
     }
 
-    pub fn field_iter(&self) -> std::slice::Iter<FlattenedField> {
+    /// Like `get_field_parser`, but the field name is compared ignoring case, e.g. `TimeStamp`
+    /// finds `timestamp`. If several fields differ only in case, the first one is used.
+    pub fn get_field_parser_ci<T: ParseableFieldType>(
+        &self,
+        flattened_field_name: &str,
+    ) -> Result<FieldParser<T>, FieldLookupError> {
+        match self.field_name_ci(flattened_field_name) {
+            Some(name) => self.get_field_parser(name),
+            None => Err(FieldLookupError::MissingField),
+        }
+    }
+
+    /// The exact name of the field whose name equals `flattened_field_name` ignoring case.
+    /// Lookups by name are exact unless they end in `_ci`.
+    pub fn field_name_ci(&self, flattened_field_name: &str) -> Option<&str> {
+        self.lowercase_names
+            .get(&flattened_field_name.to_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn field_iter(&self) -> std::slice::Iter<'_, FlattenedField> {
         self.fields.iter()
     }
 
@@ -259,9 +504,111 @@ impl FlattenedFormat {
         &self.message_name
     }
 
+    /// The flattened field names matching a pattern where `*` matches any sequence of
+    /// characters, e.g. `esc[*].esc_rpm`. All other characters match literally.
+    pub fn fields_matching(&self, pattern: &str) -> Vec<&str> {
+        self.fields
+            .iter()
+            .map(|field| field.flattened_field_name.as_str())
+            .filter(|name| matches_wildcard(pattern.as_bytes(), name.as_bytes()))
+            .collect()
+    }
+
+    /// The size of a data message of this format in bytes, including the 2 bytes of the msg_id.
+    /// This is also the value field offsets are relative to.
+    /// See `payload_size` for the size without the msg_id.
     pub fn size(&self) -> u16 {
         self.size
     }
+
+    /// Where every field is expected in a data message, e.g. "x Float at 10..14", to diagnose
+    /// data messages which do not match the format.
+    pub fn describe_layout(&self) -> String {
+        self.fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "{} {:?} at {}..{}",
+                    field.flattened_field_name,
+                    field.field_type,
+                    field.offset,
+                    field.offset as usize + field.field_type.size() as usize
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The length of `DataMessage::payload` for this format, i.e. `size()` without the 2 bytes
+    /// of the msg_id.
+    pub fn payload_size(&self) -> u16 {
+        self.size.saturating_sub(2)
+    }
+
+    /// Number of bytes of the padding at the end of the message definition. The padding is left
+    /// out of the flattened format, but some data messages include it, see
+    /// `LogParser::set_accept_trailing_padding`.
+    pub fn trailing_padding(&self) -> u16 {
+        self.trailing_padding
+    }
+
+    /// The size of the sample data in a data message in bytes, i.e. without the msg_id.
+    /// Same as `payload_size`.
+    pub fn sample_byte_size(&self) -> u16 {
+        self.payload_size()
+    }
+
+    // The position of the byte sized array array_name[0], array_name[1], ... in the message data.
+    fn byte_array_range(
+        &self,
+        array_name: &str,
+        field_type: FlattenedFieldType,
+    ) -> Option<std::ops::Range<usize>> {
+        let first = self.name_to_field.get(&format!("{}[0]", array_name))?;
+        if first.field_type != field_type || field_type.size() != 1 {
+            return None;
+        }
+        let mut len = 1;
+        while let Some(field) = self.name_to_field.get(&format!("{}[{}]", array_name, len)) {
+            if field.field_type != field_type
+                || field.offset as usize != first.offset as usize + len
+            {
+                break;
+            }
+            len += 1;
+        }
+        Some(first.offset as usize..(first.offset as usize + len))
+    }
+
+    /// Renders the format as a ULog format string, e.g. `message:uint64_t timestamp;float x[3];`
+    ///
+    /// Consecutive array elements like `x[0]`, `x[1]` are collapsed into one array field again.
+    /// Nested messages cannot be restored, their fields keep the flattened names, e.g.
+    /// `int32_t esc[0].esc_rpm`. Padding is not part of the flattened format and thus omitted.
+    pub fn to_format_string(&self) -> String {
+        let mut result = format!("{}:", self.message_name);
+        let mut i = 0;
+        while i < self.fields.len() {
+            let field = &self.fields[i];
+            let type_name = field.field_type.type_name();
+            let mut array_len = 0;
+            if let Some(base_name) = field.flattened_field_name.strip_suffix("[0]") {
+                while let Some(next) = self.fields.get(i + array_len) {
+                    if next.field_type != field.field_type
+                        || next.flattened_field_name != format!("{}[{}]", base_name, array_len)
+                    {
+                        break;
+                    }
+                    array_len += 1;
+                }
+                result += &format!("{} {}[{}];", type_name, base_name, array_len);
+            } else {
+                result += &format!("{} {};", type_name, field.flattened_field_name);
+            }
+            i += std::cmp::max(array_len, 1);
+        }
+        result
+    }
 }
 
 pub struct FieldParser<T: ParseableFieldType> {
@@ -272,7 +619,7 @@ pub struct FieldParser<T: ParseableFieldType> {
 impl<T: ParseableFieldType> FieldParser<T> {
     // data e.g. looks like the member in the DataMessage
     pub fn parse(&self, data: &[u8]) -> T {
-        return T::parse(&data[(self.offset as usize)..]);
+        T::parse(&data[(self.offset as usize)..])
     }
     pub fn offset(&self) -> u16 {
         self.offset
@@ -280,22 +627,208 @@ impl<T: ParseableFieldType> FieldParser<T> {
 }
 
 pub struct DataMessage<'a> {
-    pub msg_id: u16,
+    pub msg_id: MsgId,
     pub multi_id: MultiId,
     pub flattened_format: &'a FlattenedFormat,
     pub data: &'a [u8], // this includes the bytes of the msg_id.
 }
 
+/// A data message which owns its data, e.g. to decode it after parsing. Messages of the same
+/// instance share their format.
+#[derive(Clone, Debug)]
+pub struct OwnedDataMessage {
+    pub msg_id: MsgId,
+    pub multi_id: MultiId,
+    pub flattened_format: Arc<FlattenedFormat>,
+    pub data: Vec<u8>, // this includes the bytes of the msg_id, as in DataMessage.
+}
+
+impl OwnedDataMessage {
+    /// Borrows the message, to decode it like during parsing
+    pub fn as_data_message(&self) -> DataMessage<'_> {
+        DataMessage {
+            msg_id: self.msg_id,
+            multi_id: self.multi_id.clone(),
+            flattened_format: &self.flattened_format,
+            data: &self.data,
+        }
+    }
+}
+
+impl<'a> DataMessage<'a> {
+    /// The message data without the 2 bytes of the msg_id. Field offsets and `FieldParser`s are
+    /// relative to `data`, use `FlattenedField::payload_offset` for offsets into the payload.
+    ///
+    /// Empty if the data is too short to even hold the msg_id.
+    pub fn payload(&self) -> &'a [u8] {
+        self.data.get(2..).unwrap_or(&[])
+    }
+
+    /// Decodes only the timestamp of the sample, skipping all other fields.
+    ///
+    /// Returns None if the format has no timestamp field or the data is too short.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.flattened_format
+            .timestamp_field
+            .as_ref()?
+            .parse_timestamp(self.data)
+    }
+
+    /// Decodes a `char[N]` field as UTF-8 text, up to the first NUL character.
+    /// Invalid UTF-8 sequences are replaced.
+    ///
+    /// Returns None if there is no such char array.
+    pub fn get_string(&self, array_name: &str) -> Option<String> {
+        let range = self
+            .flattened_format
+            .byte_array_range(array_name, FlattenedFieldType::Char)?;
+        let bytes = self.data.get(range)?;
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    /// The bytes of a `char[N]` field, including any NUL characters, for char arrays which do not
+    /// hold text.
+    ///
+    /// Returns None if there is no such char array.
+    pub fn get_char_bytes(&self, array_name: &str) -> Option<Vec<u8>> {
+        let range = self
+            .flattened_format
+            .byte_array_range(array_name, FlattenedFieldType::Char)?;
+        Some(self.data.get(range)?.to_vec())
+    }
+
+    /// Reassembles a `uint8_t[N]` field, e.g. the `arguments` of an `event` message, so it can be
+    /// decoded further.
+    ///
+    /// Returns None if there is no such uint8 array.
+    pub fn get_bytes(&self, array_name: &str) -> Option<Vec<u8>> {
+        let range = self
+            .flattened_format
+            .byte_array_range(array_name, FlattenedFieldType::UInt8)?;
+        Some(self.data.get(range)?.to_vec())
+    }
+
+    /// The undecoded bytes of a single field, e.g. to check how a surprising value was decoded.
+    ///
+    /// Returns None if there is no such field or the data is too short to contain it.
+    pub fn field_bytes(&self, field_name: &str) -> Option<&'a [u8]> {
+        let field = self.flattened_format.name_to_field.get(field_name)?;
+        let start = field.offset as usize;
+        self.data
+            .get(start..(start + field.field_type.size() as usize))
+    }
+
+    /// Reads a single bit of an integer field, e.g. a flag of a packed status field. Bit 0 is the
+    /// least significant bit.
+    ///
+    /// Returns None if there is no such integer field, the bit is beyond its width or the data
+    /// is too short.
+    pub fn get_bit(&self, field_name: &str, bit: u8) -> Option<bool> {
+        match self
+            .flattened_format
+            .name_to_field
+            .get(field_name)?
+            .field_type
+        {
+            FlattenedFieldType::Float
+            | FlattenedFieldType::Double
+            | FlattenedFieldType::Bool
+            | FlattenedFieldType::Char => return None,
+            _ => (),
+        }
+        // Little endian, so the byte index grows with the bit
+        let byte = self.field_bytes(field_name)?.get(bit as usize / 8)?;
+        Some((byte >> (bit % 8)) & 1 == 1)
+    }
+}
+
+// Matches text against a pattern with `*` wildcards, backtracking to the last `*` on a mismatch.
+fn matches_wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            last_star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = last_star {
+            // Let the last `*` consume one more character.
+            p = star_p + 1;
+            t = star_t + 1;
+            last_star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
 #[derive(Debug)]
 pub enum LogStage {
     Definitions,
     Data,
 }
 
+/// A parameter value. Matches need a wildcard arm, since parameters of further types may be
+/// passed on in the future, as happened with `Raw`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ParameterMessage<'a> {
     Float(&'a str, f32, LogStage),
     Int32(&'a str, i32, LogStage),
+    /// A parameter of another type with its name, type and value bytes, only passed on by
+    /// lenient parsers, see `LogParser::set_lenient_parameter_types`
+    Raw(&'a str, &'a str, &'a [u8], LogStage),
+}
+
+/// The default value of a parameter
+pub struct DefaultParameterMessage<'a> {
+    /// Bit 0: system wide default, bit 1: default for the current configuration
+    pub default_types: u8,
+    pub parameter: ParameterMessage<'a>,
+}
+
+impl DefaultParameterMessage<'_> {
+    /// Whether the value is the default of this kind. A value may be the default of both kinds.
+    pub fn is_default_type(&self, default_type: DefaultType) -> bool {
+        let mask = match default_type {
+            DefaultType::System => 1,
+            DefaultType::Airframe => 2,
+        };
+        self.default_types & mask != 0
+    }
+}
+
+/// The kind of a default parameter value, see `DefaultParameterMessage`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DefaultType {
+    /// The system wide default
+    System,
+    /// The default for the current configuration, e.g. the airframe
+    Airframe,
+}
+
+/// An info message, containing e.g. the hardware version or the system name
+pub struct InfoMessage<'a> {
+    pub key: &'a str,
+    pub value_type: &'a str, // as written in the log, e.g. "char[10]" or "uint32_t"
+    pub value: &'a [u8],
+}
+
+/// A chunk of a multiple info message, e.g. a calibration blob. Values longer than a single
+/// message are split into chunks with the same key, all but the first are continued.
+pub struct MultipleInfoMessage<'a> {
+    pub is_continued: bool,
+    pub key: &'a str,
+    pub value_type: &'a str,
+    pub value: &'a [u8],
+}
+
+pub struct DropoutMessage {
+    pub duration_ms: u16,
 }
 
 pub struct LoggedStringMessage<'a> {
@@ -336,7 +869,7 @@ mod tests {
         let flattened_format =
             FlattenedFormat::new("message".to_string(), vec![field.clone()], 500).unwrap();
         let data_msg = DataMessage {
-            msg_id: 1,
+            msg_id: MsgId::new(1),
             multi_id: MultiId(10),
             flattened_format: &flattened_format,
             data: &data,
@@ -349,4 +882,382 @@ mod tests {
         assert_eq!(0x01000000, parser.parse(&data));
     }
 
+    #[test]
+    fn parses_timestamp_with_bounds_check() {
+        let field = TimestampField {
+            field_type: TimestampFieldType::UInt64,
+            offset: 2,
+        };
+        let data: [u8; 10] = [0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(Some(1), field.parse_timestamp(&data));
+        assert_eq!(None, field.parse_timestamp(&data[..9]));
+        assert_eq!(None, field.parse_timestamp(&data[..1]));
+    }
+
+    #[test]
+    fn renders_format_string() {
+        let field = |name: &str, field_type: FlattenedFieldType, offset: u16| FlattenedField {
+            flattened_field_name: name.to_string(),
+            field_type,
+            offset,
+        };
+        let fields = vec![
+            field("timestamp", FlattenedFieldType::UInt64, 2),
+            field("x[0]", FlattenedFieldType::Float, 10),
+            field("x[1]", FlattenedFieldType::Float, 14),
+            field("esc[0].rpm", FlattenedFieldType::Int32, 18),
+            field("esc[1].rpm", FlattenedFieldType::Int32, 22),
+        ];
+        let format = FlattenedFormat::new("message".to_string(), fields, 26).unwrap();
+        assert_eq!(
+            "message:uint64_t timestamp;float x[2];int32_t esc[0].rpm;int32_t esc[1].rpm;",
+            format.to_format_string()
+        );
+
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = crate::full_parser::read_file(&filename).unwrap();
+        let format_string = parsed_data.formats["vehicle_gps_position"].to_format_string();
+        assert!(format_string.starts_with("vehicle_gps_position:uint64_t timestamp;"));
+    }
+
+    #[test]
+    fn decodes_char_array_as_utf8() {
+        let fields = (0..8)
+            .map(|i| FlattenedField {
+                flattened_field_name: format!("name[{}]", i),
+                field_type: FlattenedFieldType::Char,
+                offset: 2 + i,
+            })
+            .collect();
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 10).unwrap();
+        let mut data = vec![0, 0];
+        data.extend_from_slice("Höhe".as_bytes());
+        data.resize(10, 0);
+        let data_msg = DataMessage {
+            msg_id: MsgId::new(0),
+            multi_id: MultiId(0),
+            flattened_format: &flattened_format,
+            data: &data,
+        };
+
+        assert_eq!(Some("Höhe".to_string()), data_msg.get_string("name"));
+        assert_eq!(None, data_msg.get_string("other"));
+        let byte_per_char: String = (0..5)
+            .map(|i| {
+                flattened_format
+                    .get_field_parser::<char>(&format!("name[{}]", i))
+                    .unwrap()
+                    .parse(&data)
+            })
+            .collect();
+        assert_ne!("Höhe", byte_per_char);
+    }
+
+    #[test]
+    fn reads_char_as_code() {
+        let fields = (0..3)
+            .map(|i| FlattenedField {
+                flattened_field_name: format!("status[{}]", i),
+                field_type: FlattenedFieldType::Char,
+                offset: 2 + i,
+            })
+            .collect();
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 5).unwrap();
+        let data = [0, 0, b'A', 0, 200];
+        let data_msg = DataMessage {
+            msg_id: MsgId::new(0),
+            multi_id: MultiId(0),
+            flattened_format: &flattened_format,
+            data: &data,
+        };
+
+        let status = flattened_format
+            .get_field_parser::<char>("status[2]")
+            .unwrap()
+            .parse(&data);
+        assert_eq!('\u{c8}', status);
+        assert_eq!(Some(200), FlattenedFieldValue::Char(status).as_u8());
+        assert_eq!(Some(65), FlattenedFieldValue::Char('A').as_u8());
+        assert_eq!(None, FlattenedFieldValue::Int8(65).as_u8());
+        assert_eq!(Some(vec![b'A', 0, 200]), data_msg.get_char_bytes("status"));
+        assert_eq!(None, data_msg.get_char_bytes("other"));
+    }
+
+    #[test]
+    fn reads_field_from_payload() {
+        let fields = vec![
+            FlattenedField {
+                flattened_field_name: "timestamp".to_string(),
+                field_type: FlattenedFieldType::UInt64,
+                offset: 2,
+            },
+            FlattenedField {
+                flattened_field_name: "x".to_string(),
+                field_type: FlattenedFieldType::UInt32,
+                offset: 10,
+            },
+        ];
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 14).unwrap();
+        let mut data = vec![5, 0];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&0xdead_beefu32.to_le_bytes());
+        let data_msg = DataMessage {
+            msg_id: MsgId::new(5),
+            multi_id: MultiId(0),
+            flattened_format: &flattened_format,
+            data: &data,
+        };
+
+        assert_eq!(12, data_msg.payload().len());
+        let field = &flattened_format.fields[1];
+        let offset = field.payload_offset() as usize;
+        assert_eq!(8, offset);
+        assert_eq!(0xdead_beef, u32::parse(&data_msg.payload()[offset..]));
+        let parser = flattened_format.get_field_parser::<u32>("x").unwrap();
+        assert_eq!(0xdead_beef, parser.parse(data_msg.data));
+
+        let too_short = DataMessage {
+            data: &data[..1],
+            ..data_msg
+        };
+        assert!(too_short.payload().is_empty());
+    }
+
+    #[test]
+    fn looks_up_fields_ignoring_case() {
+        let fields = vec![
+            FlattenedField {
+                flattened_field_name: "timestamp".to_string(),
+                field_type: FlattenedFieldType::UInt64,
+                offset: 2,
+            },
+            FlattenedField {
+                flattened_field_name: "Accel_X".to_string(),
+                field_type: FlattenedFieldType::Float,
+                offset: 10,
+            },
+        ];
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 14).unwrap();
+        assert_eq!(
+            Some("timestamp"),
+            flattened_format.field_name_ci("TimeStamp")
+        );
+        assert_eq!(Some("Accel_X"), flattened_format.field_name_ci("accel_x"));
+        assert_eq!(None, flattened_format.field_name_ci("accel_y"));
+
+        let parser = flattened_format
+            .get_field_parser_ci::<u64>("TimeStamp")
+            .unwrap();
+        assert_eq!(2, parser.offset());
+        assert!(flattened_format
+            .get_field_parser::<u64>("TimeStamp")
+            .is_err());
+        match flattened_format.get_field_parser_ci::<u64>("ACCEL_X") {
+            Err(FieldLookupError::TypeMismatch) => {}
+            _ => panic!("expected a type mismatch"),
+        }
+    }
+
+    #[test]
+    fn displays_field_values() {
+        let values = [
+            (FlattenedFieldValue::Int8(-8), "-8"),
+            (FlattenedFieldValue::UInt8(8), "8"),
+            (FlattenedFieldValue::Int16(-16), "-16"),
+            (FlattenedFieldValue::UInt16(16), "16"),
+            (FlattenedFieldValue::Int32(-32), "-32"),
+            (FlattenedFieldValue::UInt32(32), "32"),
+            (FlattenedFieldValue::Int64(-64), "-64"),
+            (
+                FlattenedFieldValue::UInt64(u64::MAX),
+                "18446744073709551615",
+            ),
+            (FlattenedFieldValue::Float(0.1), "0.1"),
+            (FlattenedFieldValue::Double(1.0 / 3.0), "0.3333333333333333"),
+            (FlattenedFieldValue::Bool(true), "true"),
+            (FlattenedFieldValue::Char('x'), "x"),
+        ];
+        for (value, expected) in values.iter() {
+            assert_eq!(*expected, value.to_string());
+        }
+    }
+
+    #[test]
+    fn decodes_field_values() {
+        let field = |field_type, offset| FlattenedField {
+            flattened_field_name: "x".to_string(),
+            field_type,
+            offset,
+        };
+        let mut data = vec![5, 0];
+        data.extend_from_slice(&(-3i16).to_le_bytes());
+        data.extend_from_slice(&1.5f64.to_le_bytes());
+        data.extend_from_slice(&[1, b'A']);
+
+        match field(FlattenedFieldType::Int16, 2).decode(&data) {
+            Some(FlattenedFieldValue::Int16(-3)) => {}
+            other => panic!("unexpected value {:?}", other),
+        }
+        match field(FlattenedFieldType::Double, 4).decode(&data) {
+            Some(FlattenedFieldValue::Double(v)) => assert_eq!(1.5, v),
+            other => panic!("unexpected value {:?}", other),
+        }
+        match field(FlattenedFieldType::Bool, 12).decode(&data) {
+            Some(FlattenedFieldValue::Bool(true)) => {}
+            other => panic!("unexpected value {:?}", other),
+        }
+        match field(FlattenedFieldType::Char, 13).decode(&data) {
+            Some(FlattenedFieldValue::Char('A')) => {}
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert!(field(FlattenedFieldType::UInt32, 12)
+            .decode(&data)
+            .is_none());
+    }
+
+    #[test]
+    fn returns_raw_field_bytes() {
+        let fields = vec![
+            FlattenedField {
+                flattened_field_name: "timestamp".to_string(),
+                field_type: FlattenedFieldType::UInt64,
+                offset: 2,
+            },
+            FlattenedField {
+                flattened_field_name: "x".to_string(),
+                field_type: FlattenedFieldType::Float,
+                offset: 10,
+            },
+        ];
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 14).unwrap();
+        let mut data = vec![5, 0];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&f32::NAN.to_le_bytes());
+        let data_msg = DataMessage {
+            msg_id: MsgId::new(5),
+            multi_id: MultiId(0),
+            flattened_format: &flattened_format,
+            data: &data,
+        };
+
+        assert_eq!(Some(&f32::NAN.to_le_bytes()[..]), data_msg.field_bytes("x"));
+        assert_eq!(8, data_msg.field_bytes("timestamp").unwrap().len());
+        assert_eq!(None, data_msg.field_bytes("y"));
+        let truncated = DataMessage {
+            data: &data[..12],
+            ..data_msg
+        };
+        assert_eq!(None, truncated.field_bytes("x"));
+    }
+
+    #[test]
+    fn reads_bits_of_integer_fields() {
+        let fields = vec![
+            FlattenedField {
+                flattened_field_name: "flags".to_string(),
+                field_type: FlattenedFieldType::UInt32,
+                offset: 2,
+            },
+            FlattenedField {
+                flattened_field_name: "x".to_string(),
+                field_type: FlattenedFieldType::Float,
+                offset: 6,
+            },
+        ];
+        let flattened_format = FlattenedFormat::new("message".to_string(), fields, 10).unwrap();
+        let mut data = vec![5, 0];
+        data.extend_from_slice(&0x0001_0004u32.to_le_bytes());
+        data.extend_from_slice(&1.0f32.to_le_bytes());
+        let data_msg = DataMessage {
+            msg_id: MsgId::new(5),
+            multi_id: MultiId(0),
+            flattened_format: &flattened_format,
+            data: &data,
+        };
+
+        assert_eq!(Some(false), data_msg.get_bit("flags", 0));
+        assert_eq!(Some(true), data_msg.get_bit("flags", 2));
+        assert_eq!(Some(true), data_msg.get_bit("flags", 16));
+        assert_eq!(Some(false), data_msg.get_bit("flags", 31));
+        assert_eq!(None, data_msg.get_bit("flags", 32));
+        assert_eq!(None, data_msg.get_bit("x", 0));
+        assert_eq!(None, data_msg.get_bit("y", 0));
+    }
+
+    #[test]
+    fn computes_sample_byte_size() {
+        let filename = format!(
+            "{}/tests/fixtures/6ba1abc7-b433-4029-b8f5-3b2bb12d3b6c.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = crate::full_parser::read_file(&filename).unwrap();
+        let format = &parsed_data.formats["vehicle_gps_position"];
+        assert_eq!(91, format.sample_byte_size());
+        assert_eq!(91, format.payload_size());
+        assert_eq!(93, format.size());
+        assert_eq!(format.size(), format.payload_size() + 2);
+    }
+
+    #[test]
+    fn selects_fields_by_wildcard() {
+        let filename = format!(
+            "{}/tests/fixtures/esc_status_log.ulg",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let parsed_data = crate::full_parser::read_file(&filename).unwrap();
+        let format = &parsed_data.formats["esc_status"];
+        let rpm_fields = format.fields_matching("esc[*].esc_rpm");
+        assert_eq!(8, rpm_fields.len());
+        assert_eq!("esc[0].esc_rpm", rpm_fields[0]);
+        assert_eq!("esc[7].esc_rpm", rpm_fields[7]);
+        assert_eq!(vec!["timestamp"], format.fields_matching("timestamp"));
+        assert_eq!(format.fields.len(), format.fields_matching("*").len());
+        assert!(format.fields_matching("esc[*].no_such_field").is_empty());
+
+        assert!(matches_wildcard(b"a*b*c", b"aXbYbZc"));
+        assert!(!matches_wildcard(b"a*b", b"aXbY"));
+    }
+
+    #[test]
+    fn validates_size_against_fields() {
+        let fields = vec![
+            FlattenedField {
+                flattened_field_name: "timestamp".to_string(),
+                field_type: FlattenedFieldType::UInt64,
+                offset: 2,
+            },
+            FlattenedField {
+                flattened_field_name: "x".to_string(),
+                field_type: FlattenedFieldType::Float,
+                offset: 10,
+            },
+        ];
+        assert_eq!(14, flattened_size(&fields));
+        assert!(FlattenedFormat::new("message".to_string(), fields.clone(), 14).is_ok());
+        assert!(FlattenedFormat::new("message".to_string(), fields, 13).is_err());
+    }
+
+    #[test]
+    fn sorts_multi_ids_and_hashes_field_types() {
+        let mut multi_ids = vec![MultiId::new(2), MultiId::new(0), MultiId::new(1)];
+        multi_ids.sort();
+        assert_eq!(
+            vec![MultiId::new(0), MultiId::new(1), MultiId::new(2)],
+            multi_ids
+        );
+
+        let mut field_counts: HashMap<FlattenedFieldType, usize> = HashMap::new();
+        for field_type in &[
+            FlattenedFieldType::Float,
+            FlattenedFieldType::UInt8,
+            FlattenedFieldType::Float,
+        ] {
+            *field_counts.entry(field_type.clone()).or_insert(0) += 1;
+        }
+        assert_eq!(2, field_counts[&FlattenedFieldType::Float]);
+        assert_eq!(1, field_counts[&FlattenedFieldType::UInt8]);
+    }
 }
@@ -0,0 +1,77 @@
+// Helpers to craft small ULog byte streams in tests.
+
+pub(crate) struct TestLog {
+    bytes: Vec<u8>,
+}
+
+impl TestLog {
+    /// A version 1 header followed by an empty FlagBits message
+    pub(crate) fn new() -> Self {
+        Self::with_header(1, 0).message(b'B', &[0; 40])
+    }
+
+    /// Only the header, without FlagBits
+    pub(crate) fn with_header(version: u8, timestamp: u64) -> Self {
+        let mut bytes = vec![85, 76, 111, 103, 1, 18, 53, version];
+        bytes.extend_from_slice(&timestamp.to_le_bytes());
+        Self { bytes }
+    }
+
+    /// No header at all, e.g. for a data section on its own
+    pub(crate) fn headerless() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    pub(crate) fn message(mut self, msg_type: u8, payload: &[u8]) -> Self {
+        self.bytes
+            .extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        self.bytes.push(msg_type);
+        self.bytes.extend_from_slice(payload);
+        self
+    }
+
+    pub(crate) fn format(self, format: &str) -> Self {
+        self.message(b'F', format.as_bytes())
+    }
+
+    pub(crate) fn add_logged_message(self, multi_id: u8, msg_id: u16, name: &str) -> Self {
+        let mut payload = vec![multi_id];
+        payload.extend_from_slice(&msg_id.to_le_bytes());
+        payload.extend_from_slice(name.as_bytes());
+        self.message(b'A', &payload)
+    }
+
+    /// key is the type and name, e.g. "int32_t SYS_AUTOSTART"
+    pub(crate) fn parameter(self, key: &str, value: [u8; 4]) -> Self {
+        let mut payload = vec![key.len() as u8];
+        payload.extend_from_slice(key.as_bytes());
+        payload.extend_from_slice(&value);
+        self.message(b'P', &payload)
+    }
+
+    pub(crate) fn default_parameter(self, default_types: u8, key: &str, value: [u8; 4]) -> Self {
+        let mut payload = vec![default_types, key.len() as u8];
+        payload.extend_from_slice(key.as_bytes());
+        payload.extend_from_slice(&value);
+        self.message(b'Q', &payload)
+    }
+
+    /// key is the type and name, e.g. "uint8_t[4] hardware_version"
+    pub(crate) fn multiple_info(self, is_continued: bool, key: &str, value: &[u8]) -> Self {
+        let mut payload = vec![is_continued as u8, key.len() as u8];
+        payload.extend_from_slice(key.as_bytes());
+        payload.extend_from_slice(value);
+        self.message(b'M', &payload)
+    }
+
+    /// payload excludes the msg_id
+    pub(crate) fn data(self, msg_id: u16, payload: &[u8]) -> Self {
+        let mut data = msg_id.to_le_bytes().to_vec();
+        data.extend_from_slice(payload);
+        self.message(b'D', &data)
+    }
+
+    pub(crate) fn bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
@@ -1,6 +1,8 @@
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, Result};
 use std::iter::*;
 
+use crate::stream_parser::LittleEndianParser;
+
 /// Convert a array of eight u8 elements into a u64
 /// Assumes little endianness.
 ///
@@ -13,7 +15,7 @@ use std::iter::*;
 pub fn as_u64_le(arr: &[u8]) -> u64 {
         arr.iter()
                 .enumerate()
-                .map(|(i, v)| (v.clone() as u64) << (8 * i))
+                .map(|(i, v)| (*v as u64) << (8 * i))
                 .sum()
 }
 
@@ -29,7 +31,7 @@ pub fn as_u64_le(arr: &[u8]) -> u64 {
 pub fn as_u32_le(arr: &[u8]) -> u32 {
         arr.iter()
                 .enumerate()
-                .map(|(i, v)| (v.clone() as u32) << (8 * i))
+                .map(|(i, v)| (*v as u32) << (8 * i))
                 .sum()
 }
 
@@ -58,7 +60,7 @@ pub fn as_i32_le(arr: &[u8]) -> i32 {
 pub fn as_u16_le(arr: &[u8]) -> u16 {
         arr.iter()
                 .enumerate()
-                .map(|(i, v)| (v.clone() as u16) << (8 * i))
+                .map(|(i, v)| (*v as u16) << (8 * i))
                 .sum()
 }
 
@@ -75,6 +77,25 @@ pub fn as_f32_le(arr: &[u8]) -> f32 {
         unsafe { *(&as_u32_le(arr) as *const u32 as *const f32) }
 }
 
+/// Read a value of any primitive type at the offset
+/// Assumes little endianness. Returns None if the slice is too short.
+///
+/// # Examples
+/// ```
+/// use px4_ulog::unpack;
+/// let arr: [u8; 10] = [9, 2, 1, 0, 0, 0, 0, 0, 0, 0];
+/// assert_eq!(unpack::read_le::<u32>(&arr, 1), Some(258));
+/// assert_eq!(unpack::read_le::<f64>(&arr, 2), Some(5e-324));
+/// assert_eq!(unpack::read_le::<u32>(&arr, 6), Some(0));
+/// assert_eq!(unpack::read_le::<u32>(&arr, 7), None);
+/// assert_eq!(unpack::read_le::<f64>(&arr, 3), None);
+/// assert_eq!(unpack::read_le::<u8>(&arr, usize::MAX), None);
+/// ```
+pub fn read_le<T: LittleEndianParser>(data: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(T::SIZE)?;
+    data.get(offset..end).map(T::parse)
+}
+
 /// Convert a u8 slice to a string
 ///
 /// # Examples
@@ -84,5 +105,5 @@ pub fn as_f32_le(arr: &[u8]) -> f32 {
 /// assert_eq!(unpack::as_str(&arr).unwrap(), "Hello");
 /// ```
 pub fn as_str(arr: &[u8]) -> Result<&str> {
-        std::str::from_utf8(arr).map_err(|_| Error::new(ErrorKind::Other, "data is not a string"))
+    std::str::from_utf8(arr).map_err(|_| Error::other("data is not a string"))
 }